@@ -0,0 +1,122 @@
+use super::*;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static TMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Replace the content of `path` with `data` without ever leaving a partially written file.
+///
+/// The data is written to a temporary file in the same directory, synced to disk, renamed over
+/// `path`, and the directory is synced as well. Permissions of an existing file are kept, and so is
+/// its owner and group as far as the current user may set them. Symbolic links are followed, so
+/// the link itself is not replaced.
+///
+/// On failure the original file is left untouched and `OVConfigError::IoError` names the stage
+/// that failed.
+pub fn write_atomic<P: AsRef<Path>>(path: P, data: &[u8]) -> Result<(), OVConfigError> {
+    let path = match std::fs::canonicalize(path.as_ref()) {
+        Ok(p) => p,
+        Err(_) => path.as_ref().to_path_buf(),
+    };
+    let dir = match path.parent() {
+        Some(d) if !d.as_os_str().is_empty() => d.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let tmp = dir.join(format!(
+        ".{}.{}.{}.tmp",
+        name,
+        std::process::id(),
+        TMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&tmp)
+        .map_err(|e| stage("create temporary file", e))?;
+    let res = write_tmp(&mut file, &path, data)
+        .and_then(|_| std::fs::rename(&tmp, &path).map_err(|e| stage("rename temporary file", e)));
+    if res.is_err() {
+        let _ = std::fs::remove_file(&tmp);
+    }
+    res?;
+
+    sync_dir(&dir).map_err(|e| stage("fsync directory", e))?;
+    Ok(())
+}
+
+/// Overwrite `path` with `data` in place, then sync it to disk.
+///
+/// Unlike `write_atomic`, only the file itself needs to be writable, not its directory, but a
+/// failure half way can leave a truncated file. Errors name the stage that failed, same as
+/// `write_atomic`.
+pub fn write_in_place<P: AsRef<Path>>(path: P, data: &[u8]) -> Result<(), OVConfigError> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)
+        .map_err(|e| stage("open file", e))?;
+    file.write_all(data).map_err(|e| stage("write file", e))?;
+    file.sync_all().map_err(|e| stage("fsync file", e))?;
+    Ok(())
+}
+
+fn write_tmp(file: &mut File, path: &Path, data: &[u8]) -> Result<(), OVConfigError> {
+    if let Ok(meta) = std::fs::metadata(path) {
+        file.set_permissions(meta.permissions())
+            .map_err(|e| stage("keep permissions", e))?;
+        keep_owner(file, &meta).map_err(|e| stage("keep ownership", e))?;
+    }
+    file.write_all(data)
+        .map_err(|e| stage("write temporary file", e))?;
+    file.sync_all()
+        .map_err(|e| stage("fsync temporary file", e))?;
+    Ok(())
+}
+
+#[cfg(unix)]
+fn keep_owner(file: &File, meta: &std::fs::Metadata) -> io::Result<()> {
+    use std::os::unix::fs::MetadataExt;
+
+    let current = file.metadata()?;
+    if current.uid() == meta.uid() && current.gid() == meta.gid() {
+        return Ok(());
+    }
+    // Only root may give a file away. Otherwise keep at least the group, if we are a member of it.
+    match std::os::unix::fs::fchown(file, Some(meta.uid()), Some(meta.gid())) {
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+            match std::os::unix::fs::fchown(file, None, Some(meta.gid())) {
+                Err(e) if e.kind() == io::ErrorKind::PermissionDenied => Ok(()),
+                res => res,
+            }
+        }
+        res => res,
+    }
+}
+
+#[cfg(not(unix))]
+fn keep_owner(_file: &File, _meta: &std::fs::Metadata) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> io::Result<()> {
+    File::open(dir)?.sync_all()
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+fn stage(stage: &str, e: io::Error) -> OVConfigError {
+    OVConfigError::IoError(io::Error::new(e.kind(), format!("{} failed: {}", stage, e)))
+}
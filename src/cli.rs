@@ -0,0 +1,70 @@
+//! Command-line overrides in the form `SECTION.key=value`.
+//!
+//! The optional `clap` feature builds clap arguments from the schema of a generated configuration.
+use super::*;
+
+/// Split an override of the form `SECTION.key=value` into its section, key and raw value.
+pub fn parse_override(arg: &str) -> Result<(&str, &str, &str), OVConfigError> {
+    let bad = || OVConfigError::BadOverride { arg: arg.into() };
+    let (path, raw) = arg.split_once('=').ok_or_else(bad)?;
+    let (section, key) = path.split_once('.').ok_or_else(bad)?;
+    let (section, key) = (section.trim(), key.trim());
+    if section.is_empty() || key.is_empty() {
+        return Err(bad());
+    }
+    Ok((section, key, raw.trim()))
+}
+
+/// Id of the `--set SECTION.key=value` argument.
+#[cfg(feature = "clap")]
+pub const SET: &str = "set";
+
+/// Build clap arguments for every key of `schema`.
+///
+/// Adds a repeatable `--set SECTION.key=value`, plus `--SECTION.key <VALUE>` for each key with
+/// its type and default value in the help text. Nothing is set unless given on the command line,
+/// so values from the file are kept.
+#[cfg(feature = "clap")]
+pub fn args(schema: &[KeyInfo]) -> Vec<clap::Arg> {
+    let mut args = vec![clap::Arg::new(SET)
+        .long(SET)
+        .value_name("SECTION.key=VALUE")
+        .action(clap::ArgAction::Append)
+        .help("Override a configuration value")];
+    for info in schema {
+        let name = format!("{}.{}", info.section, info.key);
+        let default = match &info.default {
+            Some(default) => format!("default: {}", default),
+            None => "required".into(),
+        };
+        args.push(
+            clap::Arg::new(name.clone())
+                .long(name)
+                .value_name(info.type_name)
+                .help(format!(
+                    "Override [{}]::{} [{}]",
+                    info.section, info.key, default
+                )),
+        );
+    }
+    args
+}
+
+/// Collect the overrides given through the arguments built by `args`.
+///
+/// The result can be passed to the generated `with_overrides`. Per-key arguments come first,
+/// then every `--set` in command-line order, so `--set` wins.
+#[cfg(feature = "clap")]
+pub fn overrides(matches: &clap::ArgMatches, schema: &[KeyInfo]) -> Vec<String> {
+    let mut res = Vec::new();
+    for info in schema {
+        let name = format!("{}.{}", info.section, info.key);
+        if let Ok(Some(raw)) = matches.try_get_one::<String>(&name) {
+            res.push(format!("{}={}", name, raw));
+        }
+    }
+    if let Ok(Some(values)) = matches.try_get_many::<String>(SET) {
+        res.extend(values.cloned());
+    }
+    res
+}
@@ -0,0 +1,30 @@
+use super::*;
+
+/// Implemented by every configuration schema generated by `make_config!`.
+///
+/// Lets generic tooling such as `Shared` work with any generated configuration.
+pub trait OVConfig: Sized + PartialEq {
+    /// Path the configuration was loaded from, i.e. `c_p_a_t_h`.
+    fn path(&self) -> &str;
+
+    /// Load and verify a fresh copy from `path()` in the same format.
+    ///
+    /// `self` is left untouched.
+    fn reload(&self) -> Result<Self, OVConfigError>;
+
+    /// Load every layer in order on top of the defaults, then verify the result.
+    ///
+    /// The sources are kept, so `reload()` loads every layer again.
+    fn from_sources(sources: Vec<Source>) -> Result<Self, OVConfigError>;
+
+    /// Same as `from_sources`, but unknown sections and keys are errors, see `get_config_strict`.
+    fn from_sources_strict(sources: Vec<Source>) -> Result<Self, OVConfigError>;
+
+    /// Same as `from_sources`, but also returns the unknown sections and keys as warnings.
+    fn from_sources_lenient(
+        sources: Vec<Source>,
+    ) -> Result<(Self, Vec<OVConfigError>), OVConfigError>;
+
+    /// Verify and flush to `path()`, same as the generated `flush()`.
+    fn flush(&self) -> Result<(), OVConfigError>;
+}
@@ -0,0 +1,91 @@
+use std::fmt;
+
+/// A key whose value differs between two configurations.
+///
+/// Values are serialized as JSON, with `None` as `null`, whatever the format of the configuration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    pub section: &'static str,
+    pub key: &'static str,
+    pub old: String,
+    pub new: String,
+}
+
+/// Every change between two configurations, in schema order. See the generated `diff`.
+///
+/// `Display` gives a human-readable report with one change per line, and `to_json` a JSON array.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Diff(pub Vec<Change>);
+
+impl Diff {
+    /// Whether both configurations have the same values.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Names of the sections with at least one change.
+    pub fn sections(&self) -> Vec<&'static str> {
+        let mut sections: Vec<&'static str> = Vec::new();
+        for change in &self.0 {
+            if !sections.contains(&change.section) {
+                sections.push(change.section);
+            }
+        }
+        sections
+    }
+
+    /// The changes as a JSON array of `{"section", "key", "old", "new"}` objects.
+    ///
+    /// `old` and `new` are JSON values, not strings.
+    pub fn to_json(&self) -> serde_json::Value {
+        let value = |raw: &str| {
+            serde_json::from_str::<serde_json::Value>(raw).unwrap_or_else(|_| raw.into())
+        };
+        self.0
+            .iter()
+            .map(|c| {
+                serde_json::json!({
+                    "section": c.section,
+                    "key": c.key,
+                    "old": value(&c.old),
+                    "new": value(&c.new),
+                })
+            })
+            .collect()
+    }
+}
+
+/// What a refresh changed, see the generated `refresh`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Refresh {
+    /// Names of the sections whose values changed in the configuration object.
+    pub changed: Vec<&'static str>,
+    /// Restart-only keys, marked `#[reload(restart)]`, whose value changed in the sources.
+    pub restart: Vec<Change>,
+}
+
+impl Refresh {
+    /// Whether a restart-only key changed, so the new value needs a restart to take effect.
+    pub fn needs_restart(&self) -> bool {
+        !self.restart.is_empty()
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "[{}]::{}: {} -> {}",
+            self.section, self.key, self.old, self.new
+        )
+    }
+}
+
+impl fmt::Display for Diff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for change in &self.0 {
+            writeln!(f, "{}", change)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,224 @@
+use super::*;
+use std::fmt;
+
+/// Configuration text that can be updated in place.
+///
+/// Only the lines of the keys that are set are touched. Comments, blank lines, key order and
+/// sections or keys unknown to the schema are kept as they are.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextDocument {
+    lines: Vec<String>,
+    format: Format,
+}
+
+impl TextDocument {
+    /// Wrap the text of a configuration file in the given format.
+    pub fn new(text: &str, format: Format) -> TextDocument {
+        TextDocument {
+            lines: text.lines().map(String::from).collect(),
+            format,
+        }
+    }
+
+    /// Format of this document.
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// Whether `key` is set in `[section]`.
+    pub fn contains(&self, section: &str, key: &str) -> bool {
+        self.find_key(section, key).is_some()
+    }
+
+    /// Set `key` in `[section]` to the already serialized `raw` value.
+    ///
+    /// An existing key is updated in place, keeping its indentation, separator and trailing comment.
+    /// A missing key is added after the last line of the last `[section]` in the document.
+    /// A missing section is added at the end of the document.
+    ///
+    /// Will return `OVConfigError::InlineTable` if a TOML document defines the section as an inline
+    /// table or with dotted keys, which cannot be extended with a `[section]` header.
+    pub fn set(&mut self, section: &str, key: &str, raw: &str) -> Result<(), OVConfigError> {
+        if let Some((start, end)) = self.find_key(section, key) {
+            let line = &self.lines[start];
+            let sep = line.find(['=', ':']).unwrap_or(line.len());
+            let value_start = line.len() - line[sep + 1..].trim_start().len();
+            let last = &self.lines[end - 1];
+            let from = if start == end - 1 { value_start } else { 0 };
+            let value_end = from + scan_value(&last[from..], self.format).0;
+            let comment = &last[last[..value_end].trim_end().len().max(from)..];
+            let comment = if comment.trim().is_empty() {
+                ""
+            } else {
+                comment
+            };
+            let new = format!("{}{}{}", &line[..value_start], raw, comment);
+            self.lines.splice(start..end, Some(new));
+            return Ok(());
+        }
+
+        let entry = match self.format {
+            Format::Ini => format!("{}={}", key, raw),
+            Format::Toml => format!("{} = {}", key, raw),
+        };
+        match self.sections(section).last() {
+            Some(&(header, end)) => {
+                let at = (header + 1..end)
+                    .rev()
+                    .find(|i| !self.lines[*i].trim().is_empty())
+                    .unwrap_or(header)
+                    + 1;
+                self.lines.insert(at, entry);
+            }
+            None if self.format == Format::Toml && self.defines_inline(section) => {
+                return Err(OVConfigError::InlineTable {
+                    section: section.into(),
+                });
+            }
+            None => {
+                if self.lines.last().is_some_and(|l| !l.trim().is_empty()) {
+                    self.lines.push(String::new());
+                }
+                self.lines.push(format!("[{}]", section));
+                self.lines.push(entry);
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove `key` from `[section]`, continuation lines included.
+    ///
+    /// Every assignment of the key in the section is removed. Nothing happens if it is not set.
+    pub fn remove(&mut self, section: &str, key: &str) {
+        while let Some((start, end)) = self.find_key(section, key) {
+            self.lines.drain(start..end);
+        }
+    }
+
+    /// Whether a top-level key defines `section`, i.e. `section = { .. }` or `section.key = ..`.
+    fn defines_inline(&self, section: &str) -> bool {
+        let dotted = format!("{}.", section);
+        let first = self.headers().first().map_or(self.lines.len(), |h| h.0);
+        self.lines[..first]
+            .iter()
+            .filter_map(|l| l.split_once('='))
+            .map(|(k, _)| k.trim().trim_matches('"'))
+            .any(|k| k == section || k.starts_with(&dotted))
+    }
+
+    /// Line and name of every section header, skipping the lines inside multi-line values.
+    fn headers(&self) -> Vec<(usize, &str)> {
+        let mut res = Vec::new();
+        let mut depth = 0;
+        for (i, line) in self.lines.iter().enumerate() {
+            if depth > 0 {
+                depth += scan_value(line, self.format).1;
+            } else if let Some(name) = header(line, self.format) {
+                res.push((i, name));
+            } else {
+                let line = line.trim_start();
+                if let Some(sep) = line.find(['=', ':']) {
+                    if !line.starts_with('#') && !line.starts_with(';') {
+                        depth = scan_value(&line[sep + 1..], self.format).1;
+                    }
+                }
+            }
+        }
+        res
+    }
+
+    /// Ranges of every `[section]` in the document, header line included.
+    fn sections(&self, section: &str) -> Vec<(usize, usize)> {
+        let mut res = Vec::new();
+        let mut current: Option<usize> = None;
+        for (i, name) in self.headers() {
+            if let Some(start) = current.take() {
+                res.push((start, i));
+            }
+            if name == section {
+                current = Some(i);
+            }
+        }
+        if let Some(start) = current {
+            res.push((start, self.lines.len()));
+        }
+        res
+    }
+
+    /// Lines of the last assignment of `key` in `[section]`, continuation lines included.
+    fn find_key(&self, section: &str, key: &str) -> Option<(usize, usize)> {
+        let mut found = None;
+        for (header, end) in self.sections(section) {
+            let mut i = header + 1;
+            while i < end {
+                let start = i;
+                let line = self.lines[i].trim_start();
+                i += 1;
+                let sep = match line.find(['=', ':']) {
+                    Some(sep) if !line.starts_with('#') && !line.starts_with(';') => sep,
+                    _ => continue,
+                };
+                let mut depth = scan_value(&line[sep + 1..], self.format).1;
+                while depth > 0 && i < end {
+                    depth += scan_value(&self.lines[i], self.format).1;
+                    i += 1;
+                }
+                if line[..sep].trim() == key {
+                    found = Some((start, i));
+                }
+            }
+        }
+        found
+    }
+}
+
+impl fmt::Display for TextDocument {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for line in &self.lines {
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+/// Name of the section if `line` is a section header, ignoring a trailing comment.
+fn header(line: &str, format: Format) -> Option<&str> {
+    let line = line.trim();
+    let line = line[..scan_value(line, format).0].trim_end();
+    if line.starts_with('[') {
+        Some(line.trim_start_matches('[').trim_end_matches(']').trim())
+    } else {
+        None
+    }
+}
+
+/// Scan a value up to its trailing comment.
+///
+/// Returns the byte length of the value and how many brackets it leaves open.
+fn scan_value(s: &str, format: Format) -> (usize, i32) {
+    let mut depth = 0;
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        match quote {
+            Some(q) => {
+                if escaped {
+                    escaped = false;
+                } else if c == '\\' && q == '"' {
+                    escaped = true;
+                } else if c == q {
+                    quote = None;
+                }
+            }
+            None => match c {
+                '"' | '\'' => quote = Some(c),
+                '[' | '{' => depth += 1,
+                ']' | '}' => depth -= 1,
+                '#' => return (i, depth),
+                ';' if format == Format::Ini => return (i, depth),
+                _ => (),
+            },
+        }
+    }
+    (s.len(), depth)
+}
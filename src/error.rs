@@ -1,23 +1,223 @@
+#![allow(non_local_definitions)]
+
 use super::*;
 use failure::Fail;
+use std::fmt;
 
 #[derive(Fail, Debug)]
 pub enum OVConfigError {
-    #[fail(
-        display = "OVConfigError: Bad [{}]::{}. Found: {}",
-        section, key, value
-    )]
     BadValue {
         section: String,
         key: String,
         value: String,
     },
-    #[fail(display = "ParseConfigError: {}", _0)]
     ParseConfigError(ini::ini::ParseError),
-    #[fail(display = "ParseValueError: {}", _0)]
-    ParseValueError(serde_json::error::Error),
-    #[fail(display = "IoError: {}", _0)]
+    ParseValueError {
+        section: String,
+        key: String,
+        value: String,
+        expected: &'static str,
+        line: Option<u32>,
+        reason: String,
+    },
+    SerializeValueError(serde_json::error::Error),
+    ParseTomlError(toml::de::Error),
+    SerializeTomlError(toml::ser::Error),
     IoError(std::io::Error),
+    /// A section the schema does not define, with the closest defined section.
+    UnknownSection {
+        section: String,
+        suggestion: Option<&'static str>,
+        line: Option<u32>,
+    },
+    /// A key the schema does not define, with the closest defined key of the section.
+    UnknownKey {
+        section: String,
+        key: String,
+        suggestion: Option<&'static str>,
+        line: Option<u32>,
+    },
+    /// A required key that no source set.
+    MissingKey {
+        section: String,
+        key: String,
+    },
+    /// An override that is not of the form `SECTION.key=value`.
+    BadOverride {
+        arg: String,
+    },
+    /// An error in a file pulled in by an include directive.
+    Include {
+        chain: Vec<String>,
+        error: Box<OVConfigError>,
+    },
+    /// An include cycle, or includes nested too deep.
+    BadInclude {
+        chain: Vec<String>,
+        reason: &'static str,
+    },
+    /// A TOML section written as an inline table or with dotted keys, which cannot be updated in place.
+    InlineTable {
+        section: String,
+    },
+    /// A restart-only key that changed on `refresh_hot`, and kept its old value.
+    RestartRequired {
+        section: String,
+        key: String,
+        old: String,
+        new: String,
+    },
+    /// Every error found by `verify_all`.
+    Multiple(Vec<OVConfigError>),
+}
+
+impl fmt::Display for OVConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OVConfigError::BadValue {
+                section,
+                key,
+                value,
+            } => write!(
+                f,
+                "OVConfigError: Bad [{}]::{}. Found: {}",
+                section, key, value
+            ),
+            OVConfigError::ParseConfigError(e) => write!(f, "ParseConfigError: {}", e),
+            OVConfigError::ParseValueError {
+                section,
+                key,
+                value,
+                expected,
+                line,
+                reason,
+            } => {
+                write!(f, "ParseValueError: Bad [{}]::{}", section, key)?;
+                if let Some(line) = line {
+                    write!(f, " at line {}", line)?;
+                }
+                write!(f, ". Expected: {}. Found: {}. {}", expected, value, reason)
+            }
+            OVConfigError::SerializeValueError(e) => write!(f, "SerializeValueError: {}", e),
+            OVConfigError::ParseTomlError(e) => write!(f, "ParseTomlError: {}", e),
+            OVConfigError::SerializeTomlError(e) => write!(f, "SerializeTomlError: {}", e),
+            OVConfigError::IoError(e) => write!(f, "IoError: {}", e),
+            OVConfigError::UnknownSection {
+                section,
+                suggestion,
+                line,
+            } => {
+                write!(f, "UnknownSection: [{}]", section)?;
+                write_hint(f, *line, *suggestion)
+            }
+            OVConfigError::UnknownKey {
+                section,
+                key,
+                suggestion,
+                line,
+            } => {
+                if section.is_empty() {
+                    write!(f, "UnknownKey: {} is outside any section", key)?;
+                } else {
+                    write!(f, "UnknownKey: [{}]::{}", section, key)?;
+                }
+                write_hint(f, *line, *suggestion)
+            }
+            OVConfigError::MissingKey { section, key } => {
+                write!(f, "MissingKey: [{}]::{}", section, key)
+            }
+            OVConfigError::BadOverride { arg } => write!(
+                f,
+                "BadOverride: Expected: SECTION.key=value. Found: {}",
+                arg
+            ),
+            OVConfigError::Include { chain, error } => {
+                write!(f, "IncludeError: {}: {}", chain.join(" -> "), error)
+            }
+            OVConfigError::BadInclude { chain, reason } => {
+                write!(f, "BadInclude: {}: {}", chain.join(" -> "), reason)
+            }
+            OVConfigError::InlineTable { section } => write!(
+                f,
+                "InlineTable: [{}] is an inline table or dotted keys. Write it as a [{}] table to update it.",
+                section, section
+            ),
+            OVConfigError::RestartRequired {
+                section,
+                key,
+                old,
+                new,
+            } => write!(
+                f,
+                "RestartRequired: [{}]::{} changed from {} to {}. Keeping {} until restart.",
+                section, key, old, new, old
+            ),
+            OVConfigError::Multiple(errors) => {
+                write!(f, "MultipleErrors: {} error(s)", errors.len())?;
+                for e in errors {
+                    write!(f, "\n{}", e)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+fn write_hint(f: &mut fmt::Formatter, line: Option<u32>, suggestion: Option<&str>) -> fmt::Result {
+    if let Some(line) = line {
+        write!(f, " at line {}", line)?;
+    }
+    if let Some(suggestion) = suggestion {
+        write!(f, ". Did you mean {}?", suggestion)?;
+    }
+    Ok(())
+}
+
+impl OVConfigError {
+    /// Fill in the line number of a `ParseValueError` or an unknown section or key from the
+    /// source text it was parsed from.
+    ///
+    /// Other errors are returned unchanged.
+    pub fn with_line(self, text: &str) -> OVConfigError {
+        match self {
+            OVConfigError::UnknownSection {
+                section,
+                suggestion,
+                line: None,
+            } => OVConfigError::UnknownSection {
+                line: value::find_section(text, &section),
+                section,
+                suggestion,
+            },
+            OVConfigError::UnknownKey {
+                section,
+                key,
+                suggestion,
+                line: None,
+            } => OVConfigError::UnknownKey {
+                line: value::find_line(text, &section, &key),
+                section,
+                key,
+                suggestion,
+            },
+            OVConfigError::ParseValueError {
+                section,
+                key,
+                value,
+                expected,
+                line: None,
+                reason,
+            } => OVConfigError::ParseValueError {
+                line: value::find_line(text, &section, &key),
+                section,
+                key,
+                value,
+                expected,
+                reason,
+            },
+            e => e,
+        }
+    }
 }
 
 impl From<ini::ini::Error> for OVConfigError {
@@ -29,6 +229,12 @@ impl From<ini::ini::Error> for OVConfigError {
     }
 }
 
+impl From<ini::ini::ParseError> for OVConfigError {
+    fn from(e: ini::ini::ParseError) -> OVConfigError {
+        OVConfigError::ParseConfigError(e)
+    }
+}
+
 impl From<std::io::Error> for OVConfigError {
     fn from(e: std::io::Error) -> OVConfigError {
         OVConfigError::IoError(e)
@@ -37,6 +243,18 @@ impl From<std::io::Error> for OVConfigError {
 
 impl From<serde_json::error::Error> for OVConfigError {
     fn from(e: serde_json::error::Error) -> OVConfigError {
-        OVConfigError::ParseValueError(e)
+        OVConfigError::SerializeValueError(e)
+    }
+}
+
+impl From<toml::de::Error> for OVConfigError {
+    fn from(e: toml::de::Error) -> OVConfigError {
+        OVConfigError::ParseTomlError(e)
+    }
+}
+
+impl From<toml::ser::Error> for OVConfigError {
+    fn from(e: toml::ser::Error) -> OVConfigError {
+        OVConfigError::SerializeTomlError(e)
     }
 }
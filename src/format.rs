@@ -0,0 +1,249 @@
+use super::*;
+use ini::ini::EscapePolicy;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::io::{Read, Write};
+use std::path::Path;
+
+/// Configuration file format.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Ini,
+    Toml,
+}
+
+impl Format {
+    /// Pick the format from the file extension.
+    ///
+    /// `.toml` files are parsed as TOML, everything else is parsed as INI.
+    pub fn from_path<T: AsRef<str> + ?Sized>(path: &T) -> Format {
+        match Path::new(path.as_ref()).extension() {
+            Some(ext) if ext.eq_ignore_ascii_case("toml") => Format::Toml,
+            _ => Format::Ini,
+        }
+    }
+}
+
+/// A parsed configuration document in any of the supported formats.
+///
+/// Load the file once and hand the document to every section constructor.
+#[derive(Clone)]
+pub enum Document {
+    Ini(Ini),
+    Toml(Toml),
+}
+
+impl Document {
+    /// Create an empty document in the given format.
+    pub fn new(format: Format) -> Document {
+        match format {
+            Format::Ini => Document::Ini(Ini::new()),
+            Format::Toml => Document::Toml(Toml::new()),
+        }
+    }
+
+    /// Whether `key` is set in `[section]`.
+    pub fn contains(&self, section: &str, key: &str) -> bool {
+        match self {
+            Document::Ini(ini) => ini.get_from(Some(section), key).is_some(),
+            Document::Toml(toml) => toml.get(section, key).is_some(),
+        }
+    }
+
+    /// Parse the value of `key` in `[section]` the same way as loading does.
+    ///
+    /// Returns `None` if the key is not set. See `value::parse_ini` for the arguments.
+    pub fn parse<T: DeserializeOwned>(
+        &self,
+        section: &str,
+        key: &str,
+        expected: &'static str,
+    ) -> Option<Result<T, OVConfigError>> {
+        match self {
+            Document::Ini(ini) => ini
+                .get_from(Some(section), key)
+                .map(|v| value::parse_ini(section, key, expected, v)),
+            Document::Toml(toml) => toml
+                .get(section, key)
+                .map(|v| value::parse_toml(section, key, expected, v)),
+        }
+    }
+
+    /// Set `key` in `[section]` to its raw value in `from`, or remove it if `from` does not set it.
+    pub fn copy_key(&mut self, from: Option<&Document>, section: &str, key: &str) {
+        match (self, from) {
+            (Document::Ini(ini), Some(Document::Ini(from))) => {
+                match from.get_from(Some(section), key) {
+                    Some(v) => {
+                        ini.with_section(Some(section))
+                            .set(key, value::quote_ini(v));
+                    }
+                    None => {
+                        ini.delete_from(Some(section), key);
+                    }
+                }
+            }
+            (Document::Toml(toml), Some(Document::Toml(from))) => match from.get(section, key) {
+                Some(v) => toml.set_value(section, key, v.clone()),
+                None => toml.remove(section, key),
+            },
+            (Document::Ini(ini), _) => {
+                ini.delete_from(Some(section), key);
+            }
+            (Document::Toml(toml), _) => toml.remove(section, key),
+        }
+    }
+
+    /// Format of this document.
+    pub fn format(&self) -> Format {
+        match self {
+            Document::Ini(_) => Format::Ini,
+            Document::Toml(_) => Format::Toml,
+        }
+    }
+
+    /// Parse a string in the given format.
+    pub fn load_from_str(buf: &str, format: Format) -> Result<Document, OVConfigError> {
+        Ok(match format {
+            Format::Ini => Document::Ini(Ini::load_from_str(buf)?),
+            Format::Toml => Document::Toml(Toml::load_from_str(buf)?),
+        })
+    }
+
+    /// Read and parse everything from a reader in the given format.
+    pub fn read_from<R: Read>(reader: &mut R, format: Format) -> Result<Document, OVConfigError> {
+        let mut buf = String::new();
+        reader.read_to_string(&mut buf)?;
+        Document::load_from_str(&buf, format)
+    }
+
+    /// Load and parse a file in the given format.
+    pub fn load_from_file<P: AsRef<Path>>(
+        filename: P,
+        format: Format,
+    ) -> Result<Document, OVConfigError> {
+        Ok(match format {
+            Format::Ini => Document::Ini(Ini::load_from_file(filename)?),
+            Format::Toml => Document::Toml(Toml::load_from_file(filename)?),
+        })
+    }
+
+    /// Write the document to a writer.
+    ///
+    /// INI values are written as they are, so they must be escaped already, see `value::to_raw`.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), OVConfigError> {
+        match self {
+            Document::Ini(ini) => ini.write_to_policy(writer, EscapePolicy::Nothing)?,
+            Document::Toml(toml) => toml.write_to(writer)?,
+        }
+        Ok(())
+    }
+
+    /// Write the document to a file, see `write_to`.
+    pub fn write_to_file<P: AsRef<Path>>(&self, filename: P) -> Result<(), OVConfigError> {
+        match self {
+            Document::Ini(ini) => ini.write_to_file_policy(filename, EscapePolicy::Nothing)?,
+            Document::Toml(toml) => toml.write_to_file(filename)?,
+        }
+        Ok(())
+    }
+}
+
+/// A parsed TOML configuration document.
+///
+/// Every `[SECTION]` table maps to one section struct of the generated configuration.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Toml {
+    table: toml::value::Table,
+}
+
+impl Toml {
+    /// Create an empty document.
+    pub fn new() -> Toml {
+        Default::default()
+    }
+
+    /// Load from a string.
+    pub fn load_from_str(buf: &str) -> Result<Toml, OVConfigError> {
+        Ok(Toml {
+            table: toml::from_str(buf)?,
+        })
+    }
+
+    /// Load from a file.
+    pub fn load_from_file<P: AsRef<Path>>(filename: P) -> Result<Toml, OVConfigError> {
+        Toml::load_from_str(&std::fs::read_to_string(filename)?)
+    }
+
+    /// Every section and top-level key of the document.
+    pub fn table(&self) -> &toml::value::Table {
+        &self.table
+    }
+
+    /// Get the raw value of `key` in `[section]`.
+    pub fn get(&self, section: &str, key: &str) -> Option<&toml::Value> {
+        self.table.get(section).and_then(|s| s.get(key))
+    }
+
+    /// Get the value of `key` in `[section]` converted to `T`.
+    ///
+    /// Returns `Ok(None)` if either the section or the key does not exist.
+    pub fn get_from<T: DeserializeOwned>(
+        &self,
+        section: &str,
+        key: &str,
+    ) -> Result<Option<T>, OVConfigError> {
+        match self.get(section, key) {
+            None => Ok(None),
+            Some(v) => value::parse_toml(section, key, std::any::type_name::<T>(), v).map(Some),
+        }
+    }
+
+    /// Set the value of `key` in `[section]`, creating the section if needed.
+    pub fn set<T: Serialize + ?Sized>(
+        &mut self,
+        section: &str,
+        key: &str,
+        value: &T,
+    ) -> Result<(), OVConfigError> {
+        self.set_value(section, key, toml::Value::try_from(value)?);
+        Ok(())
+    }
+
+    /// Set the raw value of `key` in `[section]`, creating the section if needed.
+    pub fn set_value(&mut self, section: &str, key: &str, value: toml::Value) {
+        let section = self
+            .table
+            .entry(section.to_string())
+            .or_insert_with(|| toml::Value::Table(Default::default()));
+        if !section.is_table() {
+            *section = toml::Value::Table(Default::default());
+        }
+        if let Some(t) = section.as_table_mut() {
+            t.insert(key.to_string(), value);
+        }
+    }
+
+    /// Set a top-level key or table of the document.
+    pub fn insert(&mut self, key: &str, value: toml::Value) {
+        self.table.insert(key.to_string(), value);
+    }
+
+    /// Remove `key` from `[section]`. Nothing happens if it is not set.
+    pub fn remove(&mut self, section: &str, key: &str) {
+        if let Some(toml::Value::Table(t)) = self.table.get_mut(section) {
+            t.remove(key);
+        }
+    }
+
+    /// Write the document to a writer.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), OVConfigError> {
+        writer.write_all(toml::to_string(&self.table)?.as_bytes())?;
+        Ok(())
+    }
+
+    /// Write the document to a file.
+    pub fn write_to_file<P: AsRef<Path>>(&self, filename: P) -> Result<(), OVConfigError> {
+        self.write_to(&mut std::fs::File::create(filename)?)
+    }
+}
@@ -0,0 +1,179 @@
+//! Include directives inside configuration files.
+//!
+//! A file can pull in other files with `@include = common.ini` before its first section, or
+//! with any key of an `[include]` section if the schema has none. TOML files use
+//! `"@include" = "common.toml"` or an `[include]` table, and the value may also be an array of
+//! paths.
+//!
+//! Relative paths resolve from the directory of the including file. Included files are applied
+//! before the file that includes them, so keys set in the including file win.
+use super::*;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// How deep includes may be nested.
+pub const MAX_DEPTH: usize = 16;
+
+/// One file to apply, in the order returned by `resolve`.
+#[derive(Clone)]
+pub struct File {
+    /// Path of the file, preceded by the paths of the files that included it.
+    pub chain: Vec<String>,
+    pub text: String,
+    /// `text` parsed in its format.
+    pub doc: Document,
+}
+
+/// Read `path` in `format` and every file it includes, recursively.
+///
+/// Errors in included files are returned as `OVConfigError::Include`, and include cycles or
+/// nesting deeper than `MAX_DEPTH` as `OVConfigError::BadInclude`, both naming the include chain.
+/// Errors in `path` itself are returned as they are. `section` is whether an `[include]` section
+/// holds include directives, which is only the case if the schema does not define one.
+pub fn resolve(path: &str, format: Format, section: bool) -> Result<Vec<File>, OVConfigError> {
+    let mut res = Vec::new();
+    let mut seen = Vec::new();
+    resolve_impl(vec![path.to_string()], format, section, &mut seen, &mut res)?;
+    Ok(res)
+}
+
+fn resolve_impl(
+    chain: Vec<String>,
+    format: Format,
+    section: bool,
+    seen: &mut Vec<PathBuf>,
+    res: &mut Vec<File>,
+) -> Result<(), OVConfigError> {
+    let path = Path::new(chain.last().unwrap());
+    let id = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    if seen.contains(&id) {
+        return Err(OVConfigError::BadInclude {
+            chain,
+            reason: "include cycle",
+        });
+    }
+    if chain.len() > MAX_DEPTH + 1 {
+        return Err(OVConfigError::BadInclude {
+            chain,
+            reason: "includes nested too deep",
+        });
+    }
+
+    let wrap = |chain: &[String], e: OVConfigError| match chain.len() {
+        1 => e,
+        _ => OVConfigError::Include {
+            chain: chain.to_vec(),
+            error: Box::new(e),
+        },
+    };
+    let text = std::fs::read_to_string(path).map_err(|e| wrap(&chain, e.into()))?;
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+
+    let doc = Document::load_from_str(&text, format).map_err(|e| wrap(&chain, e))?;
+
+    seen.push(id);
+    for include in includes(&text, &doc, section) {
+        let include = dir.join(include).to_string_lossy().into_owned();
+        let format = Format::from_path(&include);
+        let mut chain = chain.clone();
+        chain.push(include);
+        resolve_impl(chain, format, section, seen, res)?;
+    }
+    seen.pop();
+
+    res.push(File { chain, text, doc });
+    Ok(())
+}
+
+/// Write `doc` to `writer` with the include directives of `text`, the file it replaces.
+///
+/// `old` is `text` already parsed. INI directives are copied line by line in order, since a
+/// document only holds one value per key. `section` tells whether the `[include]` section holds
+/// directives, i.e. the schema does not define a section of that name.
+pub fn write_with_directives<W: Write>(
+    text: &str,
+    old: Option<&Document>,
+    mut doc: Document,
+    section: bool,
+    writer: &mut W,
+) -> Result<(), OVConfigError> {
+    match (old, &mut doc) {
+        (_, Document::Ini(_)) => {
+            let directives = ini_directives(text, section);
+            for (key, value, _) in directives.iter().filter(|d| !d.2) {
+                writeln!(writer, "{}={}", key, value)?;
+            }
+            doc.write_to(writer)?;
+            let mut lines = directives.iter().filter(|d| d.2).peekable();
+            if lines.peek().is_some() {
+                writeln!(writer, "[include]")?;
+            }
+            for (key, value, _) in lines {
+                writeln!(writer, "{}={}", key, value)?;
+            }
+            Ok(())
+        }
+        (Some(Document::Toml(from)), Document::Toml(to)) => {
+            if let Some(value) = from.table().get("@include") {
+                to.insert("@include", value.clone());
+            }
+            if let Some(value) = from.table().get("include").filter(|_| section) {
+                to.insert("include", value.clone());
+            }
+            doc.write_to(writer)
+        }
+        _ => doc.write_to(writer),
+    }
+}
+
+/// Include directives of an INI `text` in order: key, raw value and whether it is in `[include]`.
+fn ini_directives(text: &str, section: bool) -> Vec<(&str, &str, bool)> {
+    let mut res = Vec::new();
+    let mut current = None;
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if line.starts_with('[') {
+            current = Some(line.trim_start_matches('[').trim_end_matches(']').trim());
+            continue;
+        }
+        if let Some((key, value)) = line.split_once(['=', ':']) {
+            let (key, value) = (key.trim(), value.trim());
+            if current.is_none() && key == "@include" {
+                res.push((key, value, false));
+            } else if section && current == Some("include") {
+                res.push((key, value, true));
+            }
+        }
+    }
+    res
+}
+
+/// Paths included by `doc`, in order. INI directives are read from `text`.
+fn includes(text: &str, doc: &Document, section: bool) -> Vec<String> {
+    let mut res = Vec::new();
+    match doc {
+        Document::Ini(_) => {
+            for (_, value, _) in ini_directives(text, section) {
+                res.push(value.trim_matches('"').trim_matches('\'').to_string());
+            }
+        }
+        Document::Toml(toml) => {
+            let mut push = |value: &toml::Value| match value {
+                toml::Value::String(s) => res.push(s.clone()),
+                toml::Value::Array(a) => {
+                    res.extend(a.iter().filter_map(|v| v.as_str()).map(String::from))
+                }
+                _ => (),
+            };
+            if let Some(value) = toml.table().get("@include") {
+                push(value);
+            }
+            if let Some(toml::Value::Table(t)) = toml.table().get("include").filter(|_| section) {
+                t.values().for_each(push);
+            }
+        }
+    }
+    res
+}
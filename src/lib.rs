@@ -77,19 +77,92 @@
 //! # Generated function [doc](../ov_config/struct.ExampleConfig.html).
 //! See the [example config](../ov_config/struct.ExampleConfig.html) for generated function docs.
 
+extern crate arc_swap;
+#[cfg(feature = "clap")]
+extern crate clap;
 extern crate failure;
 extern crate ini;
+#[cfg(feature = "watch")]
+extern crate notify;
+extern crate serde;
+#[cfg(feature = "serde")]
+#[doc(hidden)]
+pub extern crate serde as __serde;
 extern crate serde_json;
+extern crate toml;
 
+mod atomic;
+pub mod cli;
+mod config;
+mod diff;
+mod edit;
 mod error;
-
+mod format;
+pub mod include;
+mod meta;
+mod origin;
+pub mod schema;
+mod shared;
+pub mod source;
+pub mod value;
+#[cfg(feature = "watch")]
+mod watch;
+
+pub use atomic::{write_atomic, write_in_place};
+pub use config::OVConfig;
+pub use diff::{Change, Diff, Refresh};
+pub use edit::TextDocument;
 pub use error::OVConfigError;
+pub use format::{Document, Format, Toml};
 pub use ini::Ini;
+pub use meta::Meta;
+pub use origin::{Origin, Origins};
+pub use schema::KeyInfo;
+pub use shared::Shared;
+pub use source::{Layers, Source};
+#[cfg(feature = "watch")]
+pub use watch::Watcher;
 
 /// The macro used to generate the configuration schema structure.
 ///
 /// See the [crate level docs](../ov_config/index.html) for examples.
 ///
+/// An `Option<T>` key is `None` when it is absent, is parsed as `T` when present, and is left
+/// out of the file when flushed as `None`. Its default, if any, must be `None` so that it reads
+/// back the same once left out:
+///
+/// ```compile_fail,E0080
+/// # use ov_config::*;
+/// make_config!(NetConfig, NET {
+///     host: Option<String>: Some("localhost".into()) => |_| true
+/// });
+/// # fn main() {}
+/// ```
+///
+/// Any other key declared without a default value, e.g. `url: String => |x: &String| !x.is_empty()`, is
+/// required. Loading fails with `OVConfigError::MissingKey` if no source sets it, and `Default`
+/// cannot be used on a schema with required keys: build it from a file, a string or `layered()`.
+/// The type of a required key must implement `Default`, which is only used as a placeholder.
+///
+/// ```compile_fail,E0599
+/// # use ov_config::*;
+/// make_config!(DbConfig, DB {
+///     url: String => |x: &String| !x.is_empty(),
+///     password: Option<String> => |_| true
+/// });
+/// # fn main() {
+/// let config = DbConfig::default();
+/// # }
+/// ```
+///
+/// A key marked `#[reload(restart)]`, after its doc comments, only takes effect after a restart.
+/// Keys are `#[reload(hot)]` by default. `refresh()` reports the restart-only keys that changed,
+/// and `refresh_hot()` keeps their current value.
+///
+/// With the `serde` feature, the configuration and its sections implement `Serialize` and
+/// `Deserialize`. The hidden fields, such as `c_p_a_t_h`, are skipped, and missing keys take
+/// their default value. The result is not verified.
+///
 /// See the [example config](../ov_config/struct.ExampleConfig.html) for generated function docs.
 ///
 #[macro_export]
@@ -97,73 +170,352 @@ macro_rules! make_config {
     (
         $name:ident,
         $(
+            $(#[doc = $section_doc:literal])*
             $section:ident {
-                $($key:ident:$type:ty:$default_value:expr=>$closure:expr),*
+                $(
+                    $(#[doc = $key_doc:literal])*
+                    $(#[reload($mode:ident)])?
+                    $key:ident:$type:ty$(:$default_value:expr)?=>$closure:expr
+                ),*
             }
         );*
     ) => {
+        #[allow(dead_code)]
         mod ovconfig {
             use super::*;
             $(
+                $(#[doc = $section_doc])*
                 #[allow(non_camel_case_types)]
                 #[derive(Debug, PartialEq)]
                 pub struct $section{
-                    $(pub $key: $type),*
+                    $(
+                        $(#[doc = $key_doc])*
+                        pub $key: $type
+                    ),*
                 }
 
                 impl $section {
+                    /// Names of the keys in this section.
+                    pub const KEYS: &[&str] = &[$(stringify!($key)),*];
+
+                    /// Set `key` from raw text, parsed the same way as a value in an INI file.
+                    ///
+                    /// Will return `Ok(false)` if this section has no such key.
+                    pub fn set_raw(&mut self, key: &str, raw: &str) -> Result<bool, OVConfigError> {
+                        match key {
+                            $(stringify!($key) => self.$key = $crate::value::parse_ini(stringify!($section), stringify!($key), stringify!($type), raw)?,)*
+                            _ => return Ok(false),
+                        }
+                        Ok(true)
+                    }
+
+                    /// Serialize the value of `key` to the raw text written for it in the given format.
+                    ///
+                    /// Will return `Ok(None)` for an `Option` key set to `None`, which is not written,
+                    /// and `OVConfigError::UnknownKey` if this section has no such key.
+                    pub fn raw_value(&self, key: &str, format: $crate::Format) -> Result<Option<String>, OVConfigError> {
+                        match key {
+                            $(
+                                stringify!($key) if $crate::value::is_null(&self.$key) => Ok(None),
+                                stringify!($key) => Ok(Some($crate::value::to_raw(&self.$key, stringify!($type), format)?)),
+                            )*
+                            _ => Err($crate::schema::unknown_key(stringify!($section), key, Self::KEYS)),
+                        }
+                    }
+
+                    /// Value of `key` as text, serialized the same way as `flush` with strings unquoted.
+                    ///
+                    /// `None` is empty. This is the text accepted back by `set`, so like an empty value in
+                    /// an INI file, `Some("")` of an `Option<String>` key reads back as `None`.
+                    pub fn get(&self, key: &str) -> Result<String, OVConfigError> {
+                        match key {
+                            $(stringify!($key) => $crate::value::to_text(&self.$key, stringify!($type)),)*
+                            _ => Err($crate::schema::unknown_key(stringify!($section), key, Self::KEYS)),
+                        }
+                    }
+
+                    /// Parse `raw` the same way as a value in an INI file, verify it and set `key`.
+                    ///
+                    /// Returns the name of the key as defined in the schema. The key is left unchanged if
+                    /// the value cannot be parsed or is rejected by its verification closure.
+                    pub fn set(&mut self, key: &str, raw: &str) -> Result<&'static str, OVConfigError> {
+                        match key {
+                            $(
+                                stringify!($key) => {
+                                    let value: $type = $crate::value::parse_ini(stringify!($section), stringify!($key), stringify!($type), raw)?;
+                                    if !$closure(&value) {
+                                        return Err($crate::value::bad_value(stringify!($section), stringify!($key), &value));
+                                    }
+                                    self.$key = value;
+                                    Ok(stringify!($key))
+                                }
+                            )*
+                            _ => Err($crate::schema::unknown_key(stringify!($section), key, Self::KEYS)),
+                        }
+                    }
+
+                    /// Whether `key` only takes effect after a restart, i.e. is marked `#[reload(restart)]`.
+                    pub fn is_restart(key: &str) -> bool {
+                        match key {
+                            $(stringify!($key) => $crate::__ovc_restart!($($mode)?),)*
+                            _ => false,
+                        }
+                    }
+
+                    /// Swap the values of the restart-only keys with `other`.
+                    pub fn swap_restart(&mut self, other: &mut Self) {
+                        $(
+                            if $crate::__ovc_restart!($($mode)?) {
+                                std::mem::swap(&mut self.$key, &mut other.$key);
+                            }
+                        )*
+                    }
+
+                    /// Whether `key` still has its default value.
+                    #[doc(hidden)]
+                    pub fn is_default(&self, key: &str) -> bool {
+                        let base = Self::base();
+                        match key {
+                            $(stringify!($key) => self.$key == base.$key,)*
+                            _ => false,
+                        }
+                    }
+
+                    /// Every key whose value differs in `other`, with the values serialized as JSON.
+                    pub fn diff(&self, other: &Self) -> Result<Vec<$crate::Change>, OVConfigError> {
+                        let mut changes = Vec::new();
+                        $(
+                            if self.$key != other.$key {
+                                changes.push($crate::Change {
+                                    section: stringify!($section),
+                                    key: stringify!($key),
+                                    old: serde_json::to_string(&self.$key)?,
+                                    new: serde_json::to_string(&other.$key)?,
+                                });
+                            }
+                        )*
+                        Ok(changes)
+                    }
+
                     /// Verification Function
                     pub fn verify(&self) -> Result<(), OVConfigError> {
                         $(
                             if !$closure(&self.$key) {
-                                return Err(OVConfigError::BadValue{
-                                    section:stringify!($section).into(),
-                                    key:stringify!($key).into(),
-                                    value: serde_json::to_string(&self.$key).unwrap_or("UNKONWN".into())
-                                });
+                                return Err($crate::value::bad_value(stringify!($section), stringify!($key), &self.$key));
                             }
                         )*
                         Ok(())
                     }
 
+                    /// Run every verification closure and return all failures.
+                    pub fn bad_values(&self) -> Vec<OVConfigError> {
+                        let mut errors = Vec::new();
+                        $(
+                            if !$closure(&self.$key) {
+                                errors.push($crate::value::bad_value(stringify!($section), stringify!($key), &self.$key));
+                            }
+                        )*
+                        errors
+                    }
+
+                    /// Verification Function that does not stop at the first failure.
+                    ///
+                    /// Will return `OVConfigError::Multiple` with every bad value found.
+                    pub fn verify_all(&self) -> Result<(), OVConfigError> {
+                        let errors = self.bad_values();
+                        if errors.is_empty() {
+                            Ok(())
+                        } else {
+                            Err(OVConfigError::Multiple(errors))
+                        }
+                    }
+
+                    /// Read this section from the file. The format is picked from the file extension.
                     pub fn get_config<T: AsRef<str> + ?Sized>(path: &T) -> Result<Self, OVConfigError> {
-                        let ini =  Ini::load_from_file(path.as_ref())?;
+                        Self::get_config_with_format(path, $crate::Format::from_path(path))
+                    }
+
+                    /// Read this section from the file in the given format.
+                    pub fn get_config_with_format<T: AsRef<str> + ?Sized>(path: &T, format: $crate::Format) -> Result<Self, OVConfigError> {
+                        Self::from_str(&std::fs::read_to_string(path.as_ref())?, format)
+                    }
+
+                    /// Read this section from a string in the given format.
+                    pub fn from_str(s: &str, format: $crate::Format) -> Result<Self, OVConfigError> {
+                        Self::from_document(&$crate::Document::load_from_str(s, format)?)
+                            .map_err(|e| e.with_line(s))
+                    }
+
+                    /// Read this section from a reader in the given format.
+                    pub fn from_reader<R: std::io::Read>(mut reader: R, format: $crate::Format) -> Result<Self, OVConfigError> {
+                        let mut s = String::new();
+                        reader.read_to_string(&mut s)?;
+                        Self::from_str(&s, format)
+                    }
+
+                    /// Read this section from an already parsed document.
+                    pub fn from_document(doc: &$crate::Document) -> Result<Self, OVConfigError> {
+                        match doc {
+                            $crate::Document::Ini(ini) => Self::from_ini(ini),
+                            $crate::Document::Toml(toml) => Self::from_toml(toml),
+                        }
+                    }
+
+                    /// Read this section from an already parsed INI document.
+                    ///
+                    /// Will use default value if specific field is not found in the document.
+                    pub fn from_ini(ini: &Ini) -> Result<Self, OVConfigError> {
                         Ok(Self{
                             $(
                                 $key: match ini.get_from(Some(stringify!($section)), stringify!($key)) {
-                                    None => $default_value,
-                                    Some(v) => match stringify!($type) {
-                                        "String" | "str" => serde_json::from_str(format!("\"{}\"", v).as_ref())?,
-                                        _=> serde_json::from_str(v)?
-                                    }
+                                    None => $crate::__ovc_or_missing!([$($default_value)?], $section, $key, $type),
+                                    Some(v) => $crate::value::parse_ini(stringify!($section), stringify!($key), stringify!($type), v)?
+                                }
+                            ),*
+                        })
+                    }
+
+                    /// Read this section from an already parsed TOML document.
+                    ///
+                    /// Will use default value if specific field is not found in the document.
+                    pub fn from_toml(toml: &$crate::Toml) -> Result<Self, OVConfigError> {
+                        Ok(Self{
+                            $(
+                                $key: match toml.get(stringify!($section), stringify!($key)) {
+                                    None => $crate::__ovc_or_missing!([$($default_value)?], $section, $key, $type),
+                                    Some(v) => $crate::value::parse_toml(stringify!($section), stringify!($key), stringify!($type), v)?
                                 }
                             ),*
                         })
                     }
 
+                    /// Set every key found in the document, leaving the other keys as they are.
+                    pub fn merge_document(&mut self, doc: &$crate::Document) -> Result<(), OVConfigError> {
+                        match doc {
+                            $crate::Document::Ini(ini) => {
+                                $(
+                                    if let Some(v) = ini.get_from(Some(stringify!($section)), stringify!($key)) {
+                                        self.$key = $crate::value::parse_ini(stringify!($section), stringify!($key), stringify!($type), v)?;
+                                    }
+                                )*
+                            }
+                            $crate::Document::Toml(toml) => {
+                                $(
+                                    if let Some(v) = toml.get(stringify!($section), stringify!($key)) {
+                                        self.$key = $crate::value::parse_toml(stringify!($section), stringify!($key), stringify!($type), v)?;
+                                    }
+                                )*
+                            }
+                        }
+                        Ok(())
+                    }
+
+                    /// Write every key of this section into the document.
+                    pub fn write_document(&self, doc: &mut $crate::Document) -> Result<(), OVConfigError> {
+                        match doc {
+                            $crate::Document::Ini(ini) => {
+                                $(
+                                    if !$crate::value::is_null(&self.$key) {
+                                        ini.with_section(Some(stringify!($section)))
+                                            .set(stringify!($key), $crate::value::to_raw(&self.$key, stringify!($type), $crate::Format::Ini)?);
+                                    }
+                                )*
+                            }
+                            $crate::Document::Toml(toml) => {
+                                $(
+                                    if !$crate::value::is_null(&self.$key) {
+                                        toml.set(stringify!($section), stringify!($key), &self.$key)?;
+                                    }
+                                )*
+                            }
+                        }
+                        Ok(())
+                    }
+
+                    /// Write every key of this section into the text document, keeping its layout.
+                    ///
+                    /// Keys already in the document are only rewritten if their value differs from the one
+                    /// in `old`, the document as currently parsed. Each key is compared on its own, so a key
+                    /// of `old` that cannot be parsed is rewritten without affecting the others.
+                    /// Missing keys are always added. Keys for which `skip` returns true are left as they are.
+                    pub fn write_text(&self, old: Option<&$crate::Document>, doc: &mut $crate::TextDocument, skip: &dyn Fn(&str) -> bool) -> Result<(), OVConfigError> {
+                        $(
+                            if skip(stringify!($key)) {
+                                // Left as found in the document.
+                            } else if $crate::value::is_null(&self.$key) {
+                                doc.remove(stringify!($section), stringify!($key));
+                            } else {
+                                let same = old
+                                    .and_then(|d| d.parse::<$type>(stringify!($section), stringify!($key), stringify!($type)))
+                                    .and_then(Result::ok)
+                                    .is_some_and(|v| v == self.$key);
+                                if !same || !doc.contains(stringify!($section), stringify!($key)) {
+                                    let raw = $crate::value::to_raw(&self.$key, stringify!($type), doc.format())?;
+                                    doc.set(stringify!($section), stringify!($key), &raw)?;
+                                }
+                            }
+                        )*
+                        Ok(())
+                    }
+
+                    /// Serialize this section to a string in the given format.
+                    pub fn to_string(&self, format: $crate::Format) -> Result<String, OVConfigError> {
+                        let mut buf = Vec::new();
+                        self.write_to(&mut buf, format)?;
+                        Ok(String::from_utf8_lossy(&buf).into_owned())
+                    }
+
+                    /// Serialize this section to a writer in the given format.
+                    pub fn write_to<W: std::io::Write>(&self, mut writer: W, format: $crate::Format) -> Result<(), OVConfigError> {
+                        let mut doc = $crate::Document::new(format);
+                        self.write_document(&mut doc)?;
+                        doc.write_to(&mut writer)
+                    }
                 }
 
-                impl Default for $section {
-                    fn default() -> Self {
+                impl $section {
+                    /// Default values, with a placeholder for required keys.
+                    #[doc(hidden)]
+                    pub fn base() -> Self {
                         Self {
-                            $($key: $default_value),*
+                            $($key: $crate::__ovc_default!([$($default_value)?], $type)),*
                         }
                     }
                 }
+
+                $($crate::__ovc_check_default!($section, $key, $type, [$($default_value)?]);)*
+                $crate::__ovc_impl_default!($section; $($type [$($default_value)?]),*);
+                $crate::__ovc_serde_section!($section; $($key: $type [$($default_value)?]),*);
             )*
         }
 
+        $crate::__ovc_impl_default!($name; $($($type [$($default_value)?]),*),*);
+        $crate::__ovc_serde!($name; $($section),*);
+
         #[allow(non_camel_case_types)]
         #[allow(non_snake_case)]
-        #[derive(Debug, Default, PartialEq)]
+        #[derive(Debug)]
         /// Configuration schema struct.
         ///
         /// Basically is a struct of all sections. User will need to use `Config.Section.Key` to access value.
         pub struct $name {
             pub c_p_a_t_h: String,
-            $(pub $section: ovconfig::$section,)*
+            #[doc(hidden)]
+            pub m_e_t_a: $crate::Meta,
+            $(
+                $(#[doc = $section_doc])*
+                pub $section: ovconfig::$section,
+            )*
+        }
+
+        /// Compares the path and the values, not how the configuration was loaded.
+        impl PartialEq for $name {
+            fn eq(&self, other: &Self) -> bool {
+                self.c_p_a_t_h == other.c_p_a_t_h $(&& self.$section == other.$section)*
+            }
         }
 
+        #[allow(dead_code)]
         impl $name {
             /// Sanity check convenience function
             ///
@@ -174,11 +526,130 @@ macro_rules! make_config {
                 Ok(())
             }
 
-            fn get_config_impl<T:AsRef<str> + ?Sized>(path: &T) -> Result<Self, OVConfigError> {
-                Ok(Self {
-                    c_p_a_t_h: path.as_ref().into(),
-                    $($section: ovconfig::$section::get_config(&path)?,)*
-                })
+            /// Sanity check convenience function that does not stop at the first failure.
+            ///
+            /// This function will exec every closure in every section.
+            /// Will return `OVConfigError::Multiple` listing every bad value found,
+            /// e.g. after `get_config_no_verify` so that all bad keys can be reported at once.
+            pub fn verify_all(&self) -> Result<(), OVConfigError> {
+                let mut errors = Vec::new();
+                $(errors.extend(self.$section.bad_values());)*
+                if errors.is_empty() {
+                    Ok(())
+                } else {
+                    Err(OVConfigError::Multiple(errors))
+                }
+            }
+
+            /// Format used to read and write `c_p_a_t_h`.
+            ///
+            /// Will use the format given when loading if set, otherwise the format is picked from the file extension.
+            pub fn format(&self) -> $crate::Format {
+                self.m_e_t_a.format.unwrap_or_else(|| $crate::Format::from_path(&self.c_p_a_t_h))
+            }
+
+            /// Names of the sections in this schema.
+            pub const SECTIONS: &[&str] = &[$(stringify!($section)),*];
+
+            const SECTION_KEYS: &[(&str, &[&str])] = &[$((stringify!($section), ovconfig::$section::KEYS)),*];
+
+            /// Default values, with a placeholder for required keys.
+            ///
+            /// Loaders start from here, then `check_required` makes sure no placeholder is left.
+            fn base() -> Self {
+                Self {
+                    c_p_a_t_h: String::new(),
+                    m_e_t_a: Default::default(),
+                    $($section: ovconfig::$section::base(),)*
+                }
+            }
+
+            /// Make sure every required key was set by some source.
+            ///
+            /// Will return `OVConfigError::MissingKey` for the first one that was not.
+            fn check_required(&self) -> Result<(), OVConfigError> {
+                $($(
+                    if $crate::__ovc_required!([$($default_value)?], $type)
+                        && self.m_e_t_a.origins.get(stringify!($section), stringify!($key)) == $crate::Origin::Default
+                    {
+                        return Err(OVConfigError::MissingKey {
+                            section: stringify!($section).into(),
+                            key: stringify!($key).into(),
+                        });
+                    }
+                )*)*
+                Ok(())
+            }
+
+            fn get_config_impl<T:AsRef<str> + ?Sized>(path: &T, format: Option<$crate::Format>) -> Result<Self, OVConfigError> {
+                let res = Self::load_impl(path, format, &mut Vec::new())?;
+                res.check_required()?;
+                Ok(res)
+            }
+
+            /// Load `path`, adding every unknown section and key found to `unknown`.
+            fn load_impl<T:AsRef<str> + ?Sized>(path: &T, format: Option<$crate::Format>, unknown: &mut Vec<OVConfigError>) -> Result<Self, OVConfigError> {
+                let mut res = Self::base();
+                res.merge_file(path.as_ref(), true, format, unknown)?;
+                res.c_p_a_t_h = path.as_ref().into();
+                res.m_e_t_a.format = format;
+                Ok(res)
+            }
+
+            fn from_text(text: &str, format: $crate::Format) -> Result<Self, OVConfigError> {
+                let doc = $crate::Document::load_from_str(text, format)?;
+                let mut res = Self::from_document(&doc).map_err(|e| e.with_line(text))?;
+                res.record_origins(&doc, "", Some(text));
+                Ok(res)
+            }
+
+            /// Build configuration from an already parsed document without auto verification.
+            ///
+            /// Every section is read from the same document. `c_p_a_t_h` is left empty.
+            pub fn from_document(doc: &$crate::Document) -> Result<Self, OVConfigError> {
+                let mut res = Self {
+                    c_p_a_t_h: String::new(),
+                    m_e_t_a: Default::default(),
+                    $($section: ovconfig::$section::from_document(doc)?,)*
+                };
+                res.record_origins(doc, "", None);
+                Ok(res)
+            }
+
+            /// Record every key set in `doc` as coming from `path`, with its line if `text` is given.
+            fn record_origins(&mut self, doc: &$crate::Document, path: &str, text: Option<&str>) {
+                $($(
+                    if doc.contains(stringify!($section), stringify!($key)) {
+                        let line = text.and_then(|t| $crate::value::find_line(t, stringify!($section), stringify!($key)));
+                        self.m_e_t_a.origins.set(stringify!($section), stringify!($key), $crate::Origin::File { path: path.into(), line });
+                    }
+                )*)*
+            }
+
+            /// Where the effective value of `key` in `section` came from.
+            ///
+            /// Will return `None` if the schema has no such key. Values assigned directly to the
+            /// fields are not tracked, use `set_raw` to record them.
+            pub fn origin(&self, section: &str, key: &str) -> Option<$crate::Origin> {
+                match section {
+                    $(stringify!($section) if ovconfig::$section::KEYS.contains(&key) => Some(self.m_e_t_a.origins.get(section, key)),)*
+                    _ => None,
+                }
+            }
+
+            /// Every effective value with its origin, one `SECTION.key = value (origin)` per line.
+            pub fn dump_origins(&self) -> String {
+                let mut res = String::new();
+                $($(
+                    res.push_str(&format!(
+                        "{}.{} = {} ({})\n",
+                        stringify!($section),
+                        stringify!($key),
+                        $crate::value::to_text(&self.$section.$key, stringify!($type)).unwrap_or_else(|_| "UNKNOWN".into()),
+                        self.m_e_t_a.origins.get(stringify!($section), stringify!($key)),
+                    ));
+                )*)*
+                res
             }
 
             /// Get configuration without auto verification.
@@ -191,7 +662,15 @@ macro_rules! make_config {
             /// # Return:
             /// Will return configuration object on success.
             pub fn get_config_no_verify<T:AsRef<str> + ?Sized>(path: &T) -> Result<Self, OVConfigError> {
-                Self::get_config_impl(path)
+                Self::get_config_impl(path, None)
+            }
+
+            /// Get configuration in the given format without auto verification.
+            ///
+            /// Same as `get_config_no_verify` but does not look at the file extension.
+            /// The format will be cached in the object for refresh and flush.
+            pub fn get_config_no_verify_with_format<T:AsRef<str> + ?Sized>(path: &T, format: $crate::Format) -> Result<Self, OVConfigError> {
+                Self::get_config_impl(path, Some(format))
             }
 
             /// Get configuration with auto verification.
@@ -204,405 +683,2968 @@ macro_rules! make_config {
             /// # Return:
             /// Will return configuration object on success.
             pub fn get_config<T:AsRef<str> + ?Sized>(path: &T) -> Result<Self, OVConfigError> {
-                let res = Self::get_config_impl(path)?;
+                let res = Self::get_config_impl(path, None)?;
                 res.verify()?;
                 Ok(res)
             }
 
-            fn refresh_impl(&mut self) -> Result<(), OVConfigError> {
-                $(self.$section = ovconfig::$section::get_config(&self.c_p_a_t_h)?;)*
-                Ok(())
+            /// Get configuration in the given format with auto verification.
+            ///
+            /// Same as `get_config` but does not look at the file extension.
+            /// The format will be cached in the object for refresh and flush.
+            pub fn get_config_with_format<T:AsRef<str> + ?Sized>(path: &T, format: $crate::Format) -> Result<Self, OVConfigError> {
+                let res = Self::get_config_impl(path, Some(format))?;
+                res.verify()?;
+                Ok(res)
             }
 
-            /// Read the configuration file and update current object.
+            /// Get configuration from a string without auto verification.
             ///
-            /// This function will automatically do sanity check on the value.
-            pub fn refresh(&mut self) -> Result<(), OVConfigError>{
-                self.refresh_impl()?;
-                self.verify()?;
-                Ok(())
+            /// Will use default value if specific field is not found in the string.
+            /// The format will be cached in the object for flush. `c_p_a_t_h` is left empty.
+            pub fn from_str_no_verify(s: &str, format: $crate::Format) -> Result<Self, OVConfigError> {
+                let mut res = Self::from_text(s, format)?;
+                res.m_e_t_a.format = Some(format);
+                Ok(res)
             }
 
-            /// Read the configuration file and update current object.
+            /// Get configuration from a string with auto verification.
             ///
-            /// This function will NOT automatically do sanity check on the value.
-            pub fn refresh_no_verify(&mut self) -> Result<(), OVConfigError>{
-                self.refresh_impl()?;
+            /// Will use default value if specific field is not found in the string.
+            /// The format will be cached in the object for flush. `c_p_a_t_h` is left empty.
+            pub fn from_str(s: &str, format: $crate::Format) -> Result<Self, OVConfigError> {
+                let res = Self::from_str_no_verify(s, format)?;
+                res.verify()?;
+                Ok(res)
+            }
+
+            /// Get configuration from a reader without auto verification.
+            ///
+            /// Reads everything until EOF. Otherwise same as `from_str_no_verify`.
+            pub fn from_reader_no_verify<R: std::io::Read>(mut reader: R, format: $crate::Format) -> Result<Self, OVConfigError> {
+                let mut s = String::new();
+                reader.read_to_string(&mut s)?;
+                Self::from_str_no_verify(&s, format)
+            }
+
+            /// Get configuration from a reader with auto verification.
+            ///
+            /// Reads everything until EOF. Otherwise same as `from_str`.
+            pub fn from_reader<R: std::io::Read>(reader: R, format: $crate::Format) -> Result<Self, OVConfigError> {
+                let res = Self::from_reader_no_verify(reader, format)?;
+                res.verify()?;
+                Ok(res)
+            }
+
+            /// Wrap configuration in a thread-safe handle with lock-free snapshots.
+            pub fn into_shared(self) -> $crate::Shared<Self> {
+                $crate::Shared::new(self)
+            }
+
+            fn apply_env(&mut self) -> Result<(), OVConfigError> {
+                if let Some(prefix) = self.m_e_t_a.env_prefix.clone() {
+                    self.apply_env_prefix(&prefix)?;
+                }
                 Ok(())
             }
 
-            fn flush_impl(&self) -> Result<(), OVConfigError> {
-                let mut conf = Ini::new();
+            fn apply_env_prefix(&mut self, prefix: &str) -> Result<(), OVConfigError> {
                 $(
-                    conf.with_section(Some(stringify!($section).to_string()))
-                        $(.set(stringify!($key), serde_json::to_string(&self.$section.$key)?))*
-                );*;
-
-                conf.write_to_file(&self.c_p_a_t_h)?;
+                    for key in ovconfig::$section::KEYS {
+                        let name = $crate::value::env_name(prefix, stringify!($section), key);
+                        if let Ok(raw) = std::env::var(&name) {
+                            self.$section.set_raw(key, &raw)?;
+                            self.m_e_t_a.origins.set(stringify!($section), key, $crate::Origin::Env(name));
+                        }
+                    }
+                )*
                 Ok(())
             }
 
-            /// Flush whatever in configuration object to file.
+            /// Apply environment variable overrides without auto verification.
             ///
-            /// This function will automatically do sanity check on the value.
-            pub fn flush(&self) -> Result<(), OVConfigError> {
-                self.verify()?;
-                self.flush_impl()
+            /// Every key can be overridden by `<PREFIX>_<SECTION>_<KEY>` in upper case,
+            /// e.g. `OVD_SECTION1_A_I32=12` with prefix `OVD`. Values are parsed the same way as in an INI file.
+            /// The prefix is cached in the object, so refresh will apply the environment again after reading the file.
+            pub fn with_env(mut self, prefix: &str) -> Result<Self, OVConfigError> {
+                self.m_e_t_a.env_prefix = Some(prefix.into());
+                self.apply_env()?;
+                Ok(self)
             }
 
-            /// Flush whatever in configuration object to file.
+            /// Get configuration from file with environment variable overrides, with auto verification.
             ///
-            /// This function will automatically do sanity check on the value.
-            pub fn flush_no_verify(&self) -> Result<(), OVConfigError> {
-                self.flush_impl()
+            /// Environment is applied after the file and before verification, see `with_env`.
+            pub fn get_config_with_env<T:AsRef<str> + ?Sized>(path: &T, prefix: &str) -> Result<Self, OVConfigError> {
+                let res = Self::load_impl(path, None, &mut Vec::new())?.with_env(prefix)?;
+                res.check_required()?;
+                res.verify()?;
+                Ok(res)
             }
-        }
-    }
-}
 
-make_config!(ExampleConfig, Section {
-    example:String:"example".into()=>|x: &String| x.len() > 0
-});
+            /// Get configuration from file with environment variable overrides, without auto verification.
+            pub fn get_config_no_verify_with_env<T:AsRef<str> + ?Sized>(path: &T, prefix: &str) -> Result<Self, OVConfigError> {
+                let res = Self::load_impl(path, None, &mut Vec::new())?.with_env(prefix)?;
+                res.check_required()?;
+                Ok(res)
+            }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::fs::File;
-    use std::io::prelude::*;
+            /// Keys whose value was taken from the environment when loading or refreshing.
+            ///
+            /// # Return:
+            /// Will return `(section, key, variable name)` for every key whose origin is an environment
+            /// variable, in schema order. Works with `with_env` and with `layered().env(..)` alike.
+            pub fn env_overrides(&self) -> Vec<(&'static str, &'static str, String)> {
+                let mut res = Vec::new();
+                for (section, keys) in Self::SECTION_KEYS {
+                    for key in keys.iter() {
+                        if let $crate::Origin::Env(name) = self.m_e_t_a.origins.get(section, key) {
+                            res.push((*section, *key, name));
+                        }
+                    }
+                }
+                res
+            }
 
-    #[test]
-    fn ovc_test_error() {
-        let section = "section";
-        let key = "key";
-        let value = "bad_value";
+            /// Every key of the schema, with its type and default value.
+            pub fn schema() -> Vec<$crate::KeyInfo> {
+                let mut res = Vec::new();
+                $(
+                    let default = ovconfig::$section::base();
+                    $(
+                        res.push($crate::KeyInfo {
+                            section: stringify!($section),
+                            key: stringify!($key),
+                            type_name: stringify!($type),
+                            default: match $crate::__ovc_required!([$($default_value)?], $type) {
+                                true => None,
+                                false => $crate::value::to_text(&default.$key, stringify!($type)).ok(),
+                            },
+                            description: $crate::schema::doc(&[$($key_doc),*]),
+                            restart: $crate::__ovc_restart!($($mode)?),
+                        });
+                    )*
+                )*
+                res
+            }
 
-        assert_eq!(
-            format!(
-                "OVConfigError: Bad [{}]::{}. Found: {}",
-                section, key, value
-            ),
-            OVConfigError::BadValue {
-                section: section.to_string(),
-                key: key.to_string(),
-                value: value.to_string(),
+            /// Description of every section, from its doc comments.
+            pub fn section_descriptions() -> Vec<(&'static str, String)> {
+                vec![$((stringify!($section), $crate::schema::doc(&[$($section_doc),*]))),*]
             }
-            .to_string()
-        );
-    }
 
-    make_config!(TestConfig, SECTION1 {
-        a_string:String:"key1".into()=>|x: &String| x.len() > 0,
-        a_vector:Vec<i32>:vec![1, 2, 3]=>|x: &Vec<i32>| x.len() < 4
-    }; SECTION2 {
-        a_i32:i32:15=>|x: &i32| *x < 20,
-        a_bool:bool:true =>|x| vec![true, false].contains(x)
-    });
+            /// JSON Schema of this configuration.
+            ///
+            /// Sections are objects and keys are typed properties, with their default value and
+            /// description. Required keys are listed as required, and integer ranges are derived
+            /// from the integer type.
+            pub fn json_schema() -> serde_json::Value {
+                let mut defaults = Vec::new();
+                $(
+                    let base = ovconfig::$section::base();
+                    $(
+                        defaults.push(match $crate::__ovc_required!([$($default_value)?], $type) {
+                            true => None,
+                            false => serde_json::to_value(&base.$key).ok(),
+                        });
+                    )*
+                )*
+                $crate::schema::json_schema(stringify!($name), &Self::section_descriptions(), &Self::schema(), &defaults)
+            }
 
-    #[test]
-    fn ovc_test_default() {
-        let d = TestConfig {
-            ..Default::default()
-        };
+            /// A commented example file in the given format.
+            ///
+            /// Every section and key is listed with its description, type and default value.
+            /// Required keys, and `Option` keys without a value, are commented out.
+            /// Loading the sample gives the default configuration.
+            pub fn sample_config(format: $crate::Format) -> Result<String, OVConfigError> {
+                let mut out = String::new();
+                let mut info = Self::schema().into_iter();
+                $(
+                    $crate::schema::sample_section(&mut out, stringify!($section), &$crate::schema::doc(&[$($section_doc),*]));
+                    let base = ovconfig::$section::base();
+                    $(
+                        let raw = if $crate::__ovc_required!([$($default_value)?], $type) || $crate::value::is_null(&base.$key) {
+                            None
+                        } else {
+                            Some($crate::value::to_raw(&base.$key, stringify!($type), format)?)
+                        };
+                        $crate::schema::sample_key(&mut out, &info.next().unwrap(), raw.as_deref(), format);
+                    )*
+                )*
+                Ok(out)
+            }
 
-        assert_eq!(d.SECTION1.a_string, "key1");
-        assert_eq!(d.SECTION1.a_vector, vec![1, 2, 3]);
-        assert_eq!(d.SECTION2.a_i32, 15);
-        assert_eq!(d.SECTION2.a_bool, true);
-        d.verify().unwrap();
-    }
+            /// Write the commented example file to `path`. The format is picked from the file extension.
+            pub fn write_sample_config<T: AsRef<str> + ?Sized>(path: &T) -> Result<(), OVConfigError> {
+                let sample = Self::sample_config($crate::Format::from_path(path))?;
+                $crate::write_atomic(path.as_ref(), sample.as_bytes())
+            }
 
-    #[test]
-    fn ovc_test_verify() {
-        let mut d = TestConfig {
-            ..Default::default()
-        };
-        d.SECTION2.a_i32 = 50;
-        match d.verify() {
-            Ok(_) => panic!("Should not be OK"),
-            Err(e) => assert_eq!(
-                "OVConfigError: Bad [SECTION2]::a_i32. Found: 50",
-                e.to_string()
-            ),
-        }
+            /// Set `key` in `section` from raw text, parsed the same way as a value in an INI file.
+            ///
+            /// Will return `OVConfigError::UnknownSection` or `OVConfigError::UnknownKey` if the schema has no such key.
+            /// The value is not verified.
+            pub fn set_raw(&mut self, section: &str, key: &str, raw: &str) -> Result<(), OVConfigError> {
+                self.set_raw_from(section, key, raw, $crate::Origin::Set)
+            }
+
+            fn set_raw_from(&mut self, section: &str, key: &str, raw: &str, origin: $crate::Origin) -> Result<(), OVConfigError> {
+                match section {
+                    $(
+                        stringify!($section) => match ovconfig::$section::KEYS.iter().find(|k| **k == key) {
+                            Some(key) => {
+                                self.$section.set_raw(key, raw)?;
+                                self.m_e_t_a.origins.set(stringify!($section), key, origin);
+                                Ok(())
+                            }
+                            None => Err($crate::schema::unknown_key(section, key, ovconfig::$section::KEYS)),
+                        },
+                    )*
+                    _ => Err($crate::schema::unknown_section(section, Self::SECTIONS)),
+                }
+            }
+
+            /// Every key whose value differs in `other`, e.g. the configuration before and after a reload.
+            ///
+            /// Values are serialized as JSON, with `None` as `null`, whatever the format of the
+            /// configuration. Print the result for a report, or use `Diff::to_json`.
+            pub fn diff(&self, other: &Self) -> Result<$crate::Diff, OVConfigError> {
+                let mut changes = Vec::new();
+                $(changes.extend(self.$section.diff(&other.$section)?);)*
+                Ok($crate::Diff(changes))
+            }
+
+            /// Whether `key` in `section` only takes effect after a restart, i.e. is marked `#[reload(restart)]`.
+            pub fn is_restart(section: &str, key: &str) -> bool {
+                match section {
+                    $(stringify!($section) => ovconfig::$section::is_restart(key),)*
+                    _ => false,
+                }
+            }
+
+            /// Names of the sections in this schema, same as `SECTIONS`.
+            pub fn sections() -> &'static [&'static str] {
+                Self::SECTIONS
+            }
+
+            /// Names of the keys in `section`, or `None` if the schema has no such section.
+            pub fn keys(section: &str) -> Option<&'static [&'static str]> {
+                Self::SECTION_KEYS.iter().find(|(s, _)| *s == section).map(|(_, keys)| *keys)
+            }
+
+            /// Type, default value and description of `key` in `section`.
+            pub fn key_info(section: &str, key: &str) -> Option<$crate::KeyInfo> {
+                Self::schema().into_iter().find(|i| i.section == section && i.key == key)
+            }
+
+            /// Rust type of `key` in `section`, as written in the schema.
+            pub fn type_name(section: &str, key: &str) -> Option<&'static str> {
+                Self::key_info(section, key).map(|i| i.type_name)
+            }
+
+            /// Default value of `key` in `section` as text, the way `get` returns it.
+            ///
+            /// Will return `None` if the key is required or the schema has no such key.
+            pub fn default_value(section: &str, key: &str) -> Option<String> {
+                Self::key_info(section, key).and_then(|i| i.default)
+            }
+
+            /// Value of `key` in `section` as text, serialized the same way as `flush` with strings unquoted.
+            ///
+            /// Will return `OVConfigError::UnknownSection` or `OVConfigError::UnknownKey` if the schema has no such key.
+            pub fn get(&self, section: &str, key: &str) -> Result<String, OVConfigError> {
+                match section {
+                    $(stringify!($section) => self.$section.get(key),)*
+                    _ => Err($crate::schema::unknown_section(section, Self::SECTIONS)),
+                }
+            }
+
+            /// Set `key` in `section` from text, parsed the same way as a value in an INI file.
+            ///
+            /// Unlike `set_raw`, the value is verified by the closure of the key, and on any error
+            /// the configuration is left unchanged.
+            pub fn set(&mut self, section: &str, key: &str, raw: &str) -> Result<(), OVConfigError> {
+                match section {
+                    $(
+                        stringify!($section) => {
+                            let key = self.$section.set(key, raw)?;
+                            self.m_e_t_a.origins.set(stringify!($section), key, $crate::Origin::Set);
+                            Ok(())
+                        }
+                    )*
+                    _ => Err($crate::schema::unknown_section(section, Self::SECTIONS)),
+                }
+            }
+
+            fn apply_overrides(&mut self) -> Result<(), OVConfigError> {
+                let overrides = std::mem::take(&mut self.m_e_t_a.overrides);
+                let res = self.apply_override_list(&overrides);
+                self.m_e_t_a.overrides = overrides;
+                res
+            }
+
+            fn apply_override_list(&mut self, overrides: &[String]) -> Result<(), OVConfigError> {
+                for arg in overrides {
+                    let (section, key, raw) = $crate::cli::parse_override(arg)?;
+                    self.set_raw_from(section, key, raw, $crate::Origin::Cli)?;
+                }
+                Ok(())
+            }
+
+            /// Set every key found in the text, leaving the other keys as they are.
+            pub fn merge_str(&mut self, text: &str, format: $crate::Format) -> Result<(), OVConfigError> {
+                self.merge_text(text, format, "", &mut Vec::new())
+            }
+
+            fn merge_text(&mut self, text: &str, format: $crate::Format, path: &str, unknown: &mut Vec<OVConfigError>) -> Result<(), OVConfigError> {
+                let doc = $crate::Document::load_from_str(text, format)?;
+                self.merge_parsed(&doc, text, path, unknown)
+            }
+
+            /// Merge `doc`, parsed from `text`, that was read from `path`.
+            fn merge_parsed(&mut self, doc: &$crate::Document, text: &str, path: &str, unknown: &mut Vec<OVConfigError>) -> Result<(), OVConfigError> {
+                unknown.extend($crate::schema::unknown(doc, Some(text), Self::SECTION_KEYS));
+                let res: Result<(), OVConfigError> = (|| {
+                    $(self.$section.merge_document(doc)?;)*
+                    Ok(())
+                })();
+                res.map_err(|e| e.with_line(text))?;
+                self.record_origins(doc, path, Some(text));
+                Ok(())
+            }
+
+            /// Merge `path` and the files it includes, see `include`.
+            fn merge_file(&mut self, path: &str, required: bool, format: Option<$crate::Format>, unknown: &mut Vec<OVConfigError>) -> Result<(), OVConfigError> {
+                let format = format.unwrap_or_else(|| $crate::Format::from_path(path));
+                let files = match $crate::include::resolve(path, format, !Self::SECTIONS.contains(&"include")) {
+                    Ok(files) => files,
+                    Err(OVConfigError::IoError(ref e)) if !required && e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+                    Err(e) => return Err(e),
+                };
+                for file in files {
+                    let wrap = |e| match file.chain.len() {
+                        1 => e,
+                        _ => OVConfigError::Include { chain: file.chain.clone(), error: Box::new(e) },
+                    };
+                    if file.chain.len() > 1 {
+                        self.m_e_t_a.includes.push(file.chain.last().unwrap().clone());
+                    }
+                    let mut found = Vec::new();
+                    self.merge_parsed(&file.doc, &file.text, file.chain.last().unwrap(), &mut found).map_err(wrap)?;
+                    unknown.extend(found.into_iter().map(wrap));
+                }
+                Ok(())
+            }
+
+            /// Start a layered configuration, see `Layers`.
+            ///
+            /// Layers are added in order of precedence, e.g. system file, user file, drop-in directory,
+            /// environment and command line, then `build()` loads and verifies them.
+            pub fn layered() -> $crate::Layers<Self> {
+                $crate::Layers::new()
+            }
+
+            /// Load every layer in order on top of the defaults, without auto verification.
+            ///
+            /// `c_p_a_t_h` is set to the last file layer that exists, so `flush` writes there. Flush
+            /// only writes the keys set in that file or by the program, values from the other layers
+            /// stay in their own layer.
+            pub fn from_sources_no_verify(sources: Vec<$crate::Source>) -> Result<Self, OVConfigError> {
+                let res = Self::sources_impl(sources, &mut Vec::new())?;
+                res.check_required()?;
+                Ok(res)
+            }
+
+            fn sources_impl(sources: Vec<$crate::Source>, unknown: &mut Vec<OVConfigError>) -> Result<Self, OVConfigError> {
+                let mut res = Self::base();
+                for source in &sources {
+                    match source {
+                        $crate::Source::File(path) => {
+                            res.merge_file(path, true, None, unknown)?;
+                            res.c_p_a_t_h = path.clone();
+                        }
+                        $crate::Source::OptionalFile(path) => {
+                            res.merge_file(path, false, None, unknown)?;
+                            if std::path::Path::new(path).exists() {
+                                res.c_p_a_t_h = path.clone();
+                            }
+                        }
+                        $crate::Source::DropInDir(dir) => {
+                            for path in $crate::source::fragments(dir)? {
+                                res.merge_file(&path.to_string_lossy(), true, None, unknown)?;
+                            }
+                        }
+                        $crate::Source::Env(prefix) => res.apply_env_prefix(prefix)?,
+                        $crate::Source::Cli(overrides) => res.apply_override_list(overrides)?,
+                    }
+                }
+                res.m_e_t_a.sources = sources;
+                Ok(res)
+            }
+
+            /// Load every layer in order on top of the defaults, with auto verification.
+            pub fn from_sources(sources: Vec<$crate::Source>) -> Result<Self, OVConfigError> {
+                let res = Self::from_sources_no_verify(sources)?;
+                res.verify()?;
+                Ok(res)
+            }
+
+            /// Load every layer in strict mode, with auto verification.
+            ///
+            /// See `get_config_strict`.
+            pub fn from_sources_strict(sources: Vec<$crate::Source>) -> Result<Self, OVConfigError> {
+                let mut unknown = Vec::new();
+                let mut res = Self::sources_impl(sources, &mut unknown)?;
+                $crate::schema::reject(unknown)?;
+                res.check_required()?;
+                res.m_e_t_a.strict = true;
+                res.verify()?;
+                Ok(res)
+            }
+
+            /// Load every layer in lenient mode, with auto verification.
+            ///
+            /// See `get_config_lenient`.
+            pub fn from_sources_lenient(sources: Vec<$crate::Source>) -> Result<(Self, Vec<OVConfigError>), OVConfigError> {
+                let mut unknown = Vec::new();
+                let res = Self::sources_impl(sources, &mut unknown)?;
+                res.check_required()?;
+                res.verify()?;
+                Ok((res, unknown))
+            }
+
+            /// Get configuration in strict mode, with auto verification.
+            ///
+            /// Every section or key in the file, or in the files it includes, that the schema does not
+            /// define is an error, e.g. a typo that would otherwise silently leave the default value.
+            /// Will return `OVConfigError::UnknownSection` or `OVConfigError::UnknownKey`, with the closest
+            /// defined name as a suggestion, or `OVConfigError::Multiple` if there are several.
+            /// Strict mode is cached in the object, so refresh rejects unknown keys as well.
+            pub fn get_config_strict<T:AsRef<str> + ?Sized>(path: &T) -> Result<Self, OVConfigError> {
+                let mut unknown = Vec::new();
+                let mut res = Self::load_impl(path, None, &mut unknown)?;
+                $crate::schema::reject(unknown)?;
+                res.check_required()?;
+                res.m_e_t_a.strict = true;
+                res.verify()?;
+                Ok(res)
+            }
+
+            /// Get configuration in lenient mode, with auto verification.
+            ///
+            /// Same as `get_config`, but also returns the unknown sections and keys found, as in
+            /// `get_config_strict`, as warnings.
+            pub fn get_config_lenient<T:AsRef<str> + ?Sized>(path: &T) -> Result<(Self, Vec<OVConfigError>), OVConfigError> {
+                let mut unknown = Vec::new();
+                let res = Self::load_impl(path, None, &mut unknown)?;
+                res.check_required()?;
+                res.verify()?;
+                Ok((res, unknown))
+            }
+
+            /// Apply command-line overrides of the form `SECTION.key=value` without auto verification.
+            ///
+            /// Unknown sections or keys and values that cannot be parsed as the key's type are errors.
+            /// Overrides are cached in the object, so refresh will apply them again after reading the file
+            /// and the environment.
+            pub fn with_overrides<I, S>(mut self, overrides: I) -> Result<Self, OVConfigError>
+            where
+                I: IntoIterator<Item = S>,
+                S: Into<String>,
+            {
+                self.m_e_t_a.overrides.extend(overrides.into_iter().map(Into::into));
+                self.apply_overrides()?;
+                Ok(self)
+            }
+
+            fn candidate(&self, verify: bool) -> Result<Self, OVConfigError> {
+                let mut unknown = Vec::new();
+                let mut candidate = if self.m_e_t_a.sources.is_empty() {
+                    Self::load_impl(&self.c_p_a_t_h, self.m_e_t_a.format, &mut unknown)?
+                } else {
+                    Self::sources_impl(self.m_e_t_a.sources.clone(), &mut unknown)?
+                };
+                if self.m_e_t_a.strict {
+                    $crate::schema::reject(unknown)?;
+                }
+                candidate.m_e_t_a.strict = self.m_e_t_a.strict;
+                candidate.m_e_t_a.in_place = self.m_e_t_a.in_place;
+                candidate.m_e_t_a.env_prefix = self.m_e_t_a.env_prefix.clone();
+                candidate.m_e_t_a.overrides = self.m_e_t_a.overrides.clone();
+                candidate.apply_env()?;
+                candidate.apply_overrides()?;
+                candidate.check_required()?;
+                if verify {
+                    candidate.verify()?;
+                }
+                Ok(candidate)
+            }
+
+            fn refresh_impl(&mut self, verify: bool, hot: bool) -> Result<$crate::Refresh, OVConfigError> {
+                let mut candidate = self.candidate(verify)?;
+
+                let (restart, hot_changes): (Vec<_>, Vec<_>) = self.diff(&candidate)?.0
+                    .into_iter()
+                    .partition(|c| Self::is_restart(c.section, c.key));
+                let changed = if hot {
+                    $(candidate.$section.swap_restart(&mut self.$section);)*
+                    for c in &restart {
+                        candidate.m_e_t_a.origins.set(c.section, c.key, self.m_e_t_a.origins.get(c.section, c.key));
+                    }
+                    $crate::Diff(hot_changes).sections()
+                } else {
+                    $crate::Diff(hot_changes.into_iter().chain(restart.iter().cloned()).collect()).sections()
+                };
+                *self = candidate;
+                Ok($crate::Refresh { changed, restart })
+            }
+
+            /// Read the configuration file and update current object.
+            ///
+            /// This function will automatically do sanity check on the value.
+            /// The whole configuration is loaded and verified before being swapped in,
+            /// so on any error current object is left unchanged.
+            ///
+            /// # Return:
+            /// Will return the names of the sections that changed on success, and the restart-only
+            /// keys that changed, see `Refresh::needs_restart`.
+            pub fn refresh(&mut self) -> Result<$crate::Refresh, OVConfigError>{
+                self.refresh_impl(true, false)
+            }
+
+            /// Read the configuration file and update current object.
+            ///
+            /// This function will NOT automatically do sanity check on the value.
+            /// On any error current object is left unchanged.
+            ///
+            /// # Return:
+            /// Will return the names of the sections that changed on success, and the restart-only
+            /// keys that changed, see `Refresh::needs_restart`.
+            pub fn refresh_no_verify(&mut self) -> Result<$crate::Refresh, OVConfigError>{
+                self.refresh_impl(false, false)
+            }
+
+            /// Read the configuration file and apply only the keys that take effect live.
+            ///
+            /// Same as `refresh`, but restart-only keys, marked `#[reload(restart)]`, keep their
+            /// current value. Will return `OVConfigError::RestartRequired` as a warning for every
+            /// restart-only key that changed in the file.
+            pub fn refresh_hot(&mut self) -> Result<($crate::Refresh, Vec<OVConfigError>), OVConfigError>{
+                let refresh = self.refresh_impl(true, true)?;
+                let warnings = refresh.restart.iter().map(|c| OVConfigError::RestartRequired {
+                    section: c.section.into(),
+                    key: c.key.into(),
+                    old: c.old.clone(),
+                    new: c.new.clone(),
+                }).collect();
+                Ok((refresh, warnings))
+            }
+
+            fn to_document(&self, format: $crate::Format) -> Result<$crate::Document, OVConfigError> {
+                let mut doc = $crate::Document::new(format);
+                $(self.$section.write_document(&mut doc)?;)*
+                Ok(doc)
+            }
+
+            /// Whether flush writes the value of `key` to `c_p_a_t_h`.
+            ///
+            /// Values taken from the environment, the command line, another layer or an included file
+            /// stay where they came from, so the file keeps its own. Values from any other file or
+            /// string move along with `c_p_a_t_h`. With layered sources, a default is only written
+            /// once the program changed it.
+            fn persisted(&self, section: &str, key: &str) -> bool {
+                match self.m_e_t_a.origins.get(section, key) {
+                    $crate::Origin::Env(_) | $crate::Origin::Cli => false,
+                    $crate::Origin::File { path, .. } => path == self.c_p_a_t_h || !self.owned(&path),
+                    $crate::Origin::Set => true,
+                    $crate::Origin::Default => self.m_e_t_a.sources.is_empty() || match section {
+                        $(stringify!($section) => !self.$section.is_default(key),)*
+                        _ => false,
+                    },
+                }
+            }
+
+            /// Whether `path` is one of the layers or included files, which keep their own keys.
+            fn owned(&self, path: &str) -> bool {
+                self.m_e_t_a.includes.iter().any(|p| p == path)
+                    || self.m_e_t_a.sources.iter().any(|source| match source {
+                        $crate::Source::File(p) | $crate::Source::OptionalFile(p) => p == path,
+                        $crate::Source::DropInDir(dir) => std::path::Path::new(path).parent() == Some(std::path::Path::new(dir)),
+                        _ => false,
+                    })
+            }
+
+            /// Text of `c_p_a_t_h`, empty if it does not exist yet.
+            fn read_path(&self) -> Result<String, OVConfigError> {
+                match std::fs::read_to_string(&self.c_p_a_t_h) {
+                    Ok(text) => Ok(text),
+                    Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => Ok(String::new()),
+                    Err(e) => Err(e.into()),
+                }
+            }
+
+            fn flush_impl(&self) -> Result<(), OVConfigError> {
+                let format = self.format();
+                let mut doc = self.to_document(format)?;
+                let text = self.read_path()?;
+                let old = $crate::Document::load_from_str(&text, format).ok();
+                for (section, keys) in Self::SECTION_KEYS {
+                    for key in keys.iter().filter(|k| !self.persisted(section, k)) {
+                        doc.copy_key(old.as_ref(), section, key);
+                    }
+                }
+                let mut buf = Vec::new();
+                $crate::include::write_with_directives(&text, old.as_ref(), doc, !Self::SECTIONS.contains(&"include"), &mut buf)?;
+                self.write_file(&self.c_p_a_t_h, &buf)
+            }
+
+            /// Write files in place on flush instead of replacing them atomically, see `write_in_place`.
+            ///
+            /// Use it when the file is writable but its directory is not. The choice is cached in the
+            /// object and kept on refresh.
+            pub fn with_in_place_writes(mut self) -> Self {
+                self.m_e_t_a.in_place = true;
+                self
+            }
+
+            fn write_file<P: AsRef<std::path::Path>>(&self, path: P, data: &[u8]) -> Result<(), OVConfigError> {
+                if self.m_e_t_a.in_place {
+                    $crate::write_in_place(path, data)
+                } else {
+                    $crate::write_atomic(path, data)
+                }
+            }
+
+            /// Flush whatever in configuration object to file.
+            ///
+            /// The file is replaced atomically, see `write_atomic`, unless `with_in_place_writes` is set.
+            /// Keys whose value came from the environment, the command line or another file, such as
+            /// another layer or an included file, keep the value found in the file. Use `set` to
+            /// persist a new one. Include directives of the file are kept.
+            ///
+            /// This function will automatically do sanity check on the value.
+            pub fn flush(&self) -> Result<(), OVConfigError> {
+                self.verify()?;
+                self.flush_impl()
+            }
+
+            /// Flush whatever in configuration object to file.
+            ///
+            /// The file is replaced atomically, see `write_atomic`.
+            ///
+            /// This function will NOT automatically do sanity check on the value.
+            pub fn flush_no_verify(&self) -> Result<(), OVConfigError> {
+                self.flush_impl()
+            }
+
+            /// Get configuration from a main file and a drop-in directory, with auto verification.
+            ///
+            /// Fragments of `dir` are read in lexical order after the main file, and each one only
+            /// overrides the keys it sets, e.g. `service.conf` then `service.conf.d/*.conf`.
+            /// Same as a layered configuration, so `refresh` reloads the fragments as well.
+            pub fn get_config_with_drop_ins<T: AsRef<str> + ?Sized, D: AsRef<str> + ?Sized>(path: &T, dir: &D) -> Result<Self, OVConfigError> {
+                Self::layered().file(path).drop_in_dir(dir).build()
+            }
+
+            /// Get configuration from a main file and a drop-in directory, without auto verification.
+            pub fn get_config_no_verify_with_drop_ins<T: AsRef<str> + ?Sized, D: AsRef<str> + ?Sized>(path: &T, dir: &D) -> Result<Self, OVConfigError> {
+                Self::from_sources_no_verify(Self::layered().file(path).drop_in_dir(dir).sources().to_vec())
+            }
+
+            /// Write the current value of one key into the drop-in `name` of `dir`, instead of rewriting the main file.
+            ///
+            /// The fragment is created if needed, along with `dir`. Other lines of an existing
+            /// fragment are kept, and the file is replaced atomically. The format is picked from `name`.
+            ///
+            /// This function will automatically do sanity check on the value.
+            pub fn flush_drop_in<D: AsRef<std::path::Path>>(&self, dir: D, name: &str, section: &str, key: &str) -> Result<(), OVConfigError> {
+                self.verify()?;
+                let format = $crate::Format::from_path(name);
+                let raw = match section {
+                    $(
+                        stringify!($section) => self.$section.raw_value(key, format)?,
+                    )*
+                    _ => return Err($crate::schema::unknown_section(section, Self::SECTIONS)),
+                };
+
+                std::fs::create_dir_all(dir.as_ref())?;
+                let path = dir.as_ref().join(name);
+                let text = match std::fs::read_to_string(&path) {
+                    Ok(text) => text,
+                    Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+                    Err(e) => return Err(e.into()),
+                };
+                let mut doc = $crate::TextDocument::new(&text, format);
+                match raw {
+                    Some(raw) => doc.set(section, key, &raw)?,
+                    None => doc.remove(section, key),
+                }
+                self.write_file(path, doc.to_string().as_bytes())
+            }
+
+            fn flush_preserving_impl(&self) -> Result<(), OVConfigError> {
+                let format = self.format();
+                let text = self.read_path()?;
+                let old = $crate::Document::load_from_str(&text, format).ok();
+                let mut doc = $crate::TextDocument::new(&text, format);
+                $(self.$section.write_text(old.as_ref(), &mut doc, &|key| !self.persisted(stringify!($section), key))?;)*
+                self.write_file(&self.c_p_a_t_h, doc.to_string().as_bytes())
+            }
+
+            /// Flush configuration object to file, keeping the layout of the existing file.
+            ///
+            /// Only the keys whose value changed are rewritten. Comments, blank lines, key order,
+            /// unknown keys and foreign sections are kept. Missing keys are added at the end of their
+            /// section, missing sections at the end of the file. The file is replaced atomically.
+            /// Keys whose value came from another source are left as they are, same as `flush`.
+            ///
+            /// This function will automatically do sanity check on the value.
+            pub fn flush_preserving(&self) -> Result<(), OVConfigError> {
+                self.verify()?;
+                self.flush_preserving_impl()
+            }
+
+            /// Flush configuration object to file, keeping the layout of the existing file.
+            ///
+            /// This function will NOT automatically do sanity check on the value.
+            pub fn flush_preserving_no_verify(&self) -> Result<(), OVConfigError> {
+                self.flush_preserving_impl()
+            }
+
+            /// Serialize whatever in configuration object to a string in `format()`.
+            ///
+            /// This function will automatically do sanity check on the value.
+            pub fn to_string(&self) -> Result<String, OVConfigError> {
+                self.verify()?;
+                self.to_string_no_verify()
+            }
+
+            /// Serialize whatever in configuration object to a string in `format()`.
+            ///
+            /// This function will NOT automatically do sanity check on the value.
+            pub fn to_string_no_verify(&self) -> Result<String, OVConfigError> {
+                let mut buf = Vec::new();
+                self.write_to_no_verify(&mut buf)?;
+                Ok(String::from_utf8_lossy(&buf).into_owned())
+            }
+
+            /// Write whatever in configuration object to a writer in `format()`.
+            ///
+            /// This function will automatically do sanity check on the value.
+            pub fn write_to<W: std::io::Write>(&self, writer: W) -> Result<(), OVConfigError> {
+                self.verify()?;
+                self.write_to_no_verify(writer)
+            }
+
+            /// Write whatever in configuration object to a writer in `format()`.
+            ///
+            /// This function will NOT automatically do sanity check on the value.
+            pub fn write_to_no_verify<W: std::io::Write>(&self, mut writer: W) -> Result<(), OVConfigError> {
+                self.to_document(self.format())?.write_to(&mut writer)
+            }
+        }
+
+        impl $crate::OVConfig for $name {
+            fn path(&self) -> &str {
+                &self.c_p_a_t_h
+            }
+
+            fn reload(&self) -> Result<Self, OVConfigError> {
+                self.candidate(true)
+            }
+
+            fn from_sources(sources: Vec<$crate::Source>) -> Result<Self, OVConfigError> {
+                $name::from_sources(sources)
+            }
+
+            fn from_sources_strict(sources: Vec<$crate::Source>) -> Result<Self, OVConfigError> {
+                $name::from_sources_strict(sources)
+            }
+
+            fn from_sources_lenient(sources: Vec<$crate::Source>) -> Result<(Self, Vec<OVConfigError>), OVConfigError> {
+                $name::from_sources_lenient(sources)
+            }
+
+            fn flush(&self) -> Result<(), OVConfigError> {
+                $name::flush(self)
+            }
+        }
+    }
+}
+
+/// Default value of a key, or a placeholder for a required key.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __ovc_default {
+    ([$default_value:expr], $type:ty) => {
+        $default_value
+    };
+    ([], $type:ty) => {
+        <$type as Default>::default()
+    };
+}
+
+/// Fail to compile if an `Option` key has a default other than `None`.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __ovc_check_default {
+    ($section:ident, $key:ident, $type:ty, [$default_value:expr]) => {
+        const _: () = assert!(
+            $crate::value::is_valid_default(stringify!($type), stringify!($default_value)),
+            concat!(
+                "[",
+                stringify!($section),
+                "]::",
+                stringify!($key),
+                ": an Option key must default to None"
+            )
+        );
+    };
+    ($section:ident, $key:ident, $type:ty, []) => {};
+}
+
+/// Default value of a key, or a `MissingKey` error for a required key.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __ovc_or_missing {
+    ([$default_value:expr], $section:ident, $key:ident, $type:ty) => {
+        $default_value
+    };
+    ([], $section:ident, $key:ident, $type:ty) => {
+        if $crate::value::is_option(stringify!($type)) {
+            <$type as Default>::default()
+        } else {
+            return Err(OVConfigError::MissingKey {
+                section: stringify!($section).into(),
+                key: stringify!($key).into(),
+            });
+        }
+    };
+}
+
+/// Whether a key is required. `Option` keys never are.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __ovc_required {
+    ([$default_value:expr], $type:ty) => {
+        false
+    };
+    ([], $type:ty) => {
+        !$crate::value::is_option(stringify!($type))
+    };
+}
+
+/// Whether a key is marked `#[reload(restart)]`. Keys are hot by default.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __ovc_restart {
+    () => {
+        false
+    };
+    (hot) => {
+        false
+    };
+    (restart) => {
+        true
+    };
+    ($mode:ident) => {
+        compile_error!(concat!(
+            "Expected: hot or restart. Found: ",
+            stringify!($mode)
+        ))
+    };
+}
+
+/// Implement `Serialize` and `Deserialize` for a section, with the `serde` feature.
+///
+/// Every key is serialized, and missing keys take their default value on deserialization.
+#[cfg(feature = "serde")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __ovc_serde_section {
+    ($section:ident; $($key:ident: $type:ty [$($default_value:expr)?]),*) => {
+        impl $crate::__serde::Serialize for $section {
+            fn serialize<S: $crate::__serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                use $crate::__serde::ser::SerializeStruct;
+                let mut state = serializer.serialize_struct(stringify!($section), Self::KEYS.len())?;
+                $(state.serialize_field(stringify!($key), &self.$key)?;)*
+                state.end()
+            }
+        }
+
+        impl<'de> $crate::__serde::Deserialize<'de> for $section {
+            fn deserialize<D: $crate::__serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                use $crate::__serde::de::{Error, IgnoredAny, MapAccess, SeqAccess, Visitor};
+
+                struct SectionVisitor;
+
+                impl<'de> Visitor<'de> for SectionVisitor {
+                    type Value = $section;
+
+                    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                        write!(f, "section {}", stringify!($section))
+                    }
+
+                    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<$section, A::Error> {
+                        let mut res = $section::base();
+                        let mut found = Vec::new();
+                        while let Some(key) = map.next_key::<String>()? {
+                            match key.as_str() {
+                                $(stringify!($key) => {
+                                    res.$key = map.next_value::<$type>()?;
+                                    found.push(stringify!($key));
+                                })*
+                                _ => {
+                                    map.next_value::<IgnoredAny>()?;
+                                }
+                            }
+                        }
+                        $(
+                            if $crate::__ovc_required!([$($default_value)?], $type) && !found.contains(&stringify!($key)) {
+                                return Err(A::Error::missing_field(stringify!($key)));
+                            }
+                        )*
+                        Ok(res)
+                    }
+
+                    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<$section, A::Error> {
+                        let mut res = $section::base();
+                        $(
+                            match seq.next_element::<$type>()? {
+                                Some(v) => res.$key = v,
+                                None if $crate::__ovc_required!([$($default_value)?], $type) => {
+                                    return Err(A::Error::missing_field(stringify!($key)));
+                                }
+                                None => (),
+                            }
+                        )*
+                        Ok(res)
+                    }
+                }
+
+                deserializer.deserialize_struct(stringify!($section), Self::KEYS, SectionVisitor)
+            }
+        }
+    };
+}
+
+#[cfg(not(feature = "serde"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __ovc_serde_section {
+    ($($rest:tt)*) => {};
+}
+
+/// Implement `Serialize` and `Deserialize` for a configuration, with the `serde` feature.
+///
+/// Only the sections are serialized. The hidden fields, such as `c_p_a_t_h`, are skipped and
+/// take their default value on deserialization, as do missing sections.
+#[cfg(feature = "serde")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __ovc_serde {
+    ($name:ident; $($section:ident),*) => {
+        impl $crate::__serde::Serialize for $name {
+            fn serialize<S: $crate::__serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                use $crate::__serde::ser::SerializeStruct;
+                let mut state = serializer.serialize_struct(stringify!($name), Self::SECTIONS.len())?;
+                $(state.serialize_field(stringify!($section), &self.$section)?;)*
+                state.end()
+            }
+        }
+
+        impl<'de> $crate::__serde::Deserialize<'de> for $name {
+            fn deserialize<D: $crate::__serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                use $crate::__serde::de::{Error, IgnoredAny, MapAccess, SeqAccess, Visitor};
+
+                struct ConfigVisitor;
+
+                impl ConfigVisitor {
+                    /// Fail if a section left out has a required key.
+                    fn check_missing<E: Error>(found: &[&str]) -> Result<(), E> {
+                        match $name::schema().into_iter().find(|i| i.default.is_none() && !found.contains(&i.section)) {
+                            Some(info) => Err(E::missing_field(info.key)),
+                            None => Ok(()),
+                        }
+                    }
+                }
+
+                impl<'de> Visitor<'de> for ConfigVisitor {
+                    type Value = $name;
+
+                    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                        write!(f, "configuration {}", stringify!($name))
+                    }
+
+                    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<$name, A::Error> {
+                        let mut res = $name::base();
+                        let mut found = Vec::new();
+                        while let Some(section) = map.next_key::<String>()? {
+                            match section.as_str() {
+                                $(stringify!($section) => {
+                                    res.$section = map.next_value()?;
+                                    found.push(stringify!($section));
+                                })*
+                                _ => {
+                                    map.next_value::<IgnoredAny>()?;
+                                }
+                            }
+                        }
+                        Self::check_missing(&found)?;
+                        Ok(res)
+                    }
+
+                    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<$name, A::Error> {
+                        let mut res = $name::base();
+                        let mut found = Vec::new();
+                        $(
+                            if let Some(section) = seq.next_element()? {
+                                res.$section = section;
+                                found.push(stringify!($section));
+                            }
+                        )*
+                        Self::check_missing(&found)?;
+                        Ok(res)
+                    }
+                }
+
+                deserializer.deserialize_struct(stringify!($name), Self::SECTIONS, ConfigVisitor)
+            }
+        }
+    };
+}
+
+#[cfg(not(feature = "serde"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __ovc_serde {
+    ($($rest:tt)*) => {};
+}
+
+/// Implement `Default`, usable only if every key without a default value is an `Option`.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __ovc_impl_default {
+    ($name:ident; $($type:ty [$($default_value:expr)?]),*) => {
+        impl Default for $name
+        where
+            $(for<'a> $crate::value::Key<'a, $crate::__ovc_key_type!([$($default_value)?], $type)>: $crate::value::Optional,)*
+        {
+            fn default() -> Self {
+                Self::base()
+            }
+        }
+    };
+}
+
+/// Type checked by `Default` for a key: always an `Option` if the key has a default value.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __ovc_key_type {
+    ([$default_value:expr], $type:ty) => {
+        Option<$type>
+    };
+    ([], $type:ty) => {
+        $type
+    };
+}
+
+make_config!(ExampleConfig,
+    /// An example section.
+    Section {
+        /// An example key.
+        example:String:"example".into()=>|x: &String| !x.is_empty()
+    }
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::io::prelude::*;
+
+    #[test]
+    fn ovc_test_error() {
+        let section = "section";
+        let key = "key";
+        let value = "bad_value";
+
+        assert_eq!(
+            format!(
+                "OVConfigError: Bad [{}]::{}. Found: {}",
+                section, key, value
+            ),
+            OVConfigError::BadValue {
+                section: section.to_string(),
+                key: key.to_string(),
+                value: value.to_string(),
+            }
+            .to_string()
+        );
+    }
+
+    make_config!(TestConfig,
+    /// Strings and lists.
+    SECTION1 {
+        /// Must not be empty.
+        a_string:String:"key1".into()=>|x: &String| !x.is_empty(),
+        /// At most three items.
+        ///
+        /// Written as a JSON array in INI files.
+        a_vector:Vec<i32>:vec![1, 2, 3]=>|x: &Vec<i32>| x.len() < 4
+    }; SECTION2 {
+        a_i32:i32:15=>|x: &i32| *x < 20,
+        a_bool:bool:true =>|x| [true, false].contains(x)
+    });
+
+    #[test]
+    fn ovc_test_default() {
+        let d = TestConfig {
+            ..Default::default()
+        };
+
+        assert_eq!(d.SECTION1.a_string, "key1");
+        assert_eq!(d.SECTION1.a_vector, vec![1, 2, 3]);
+        assert_eq!(d.SECTION2.a_i32, 15);
+        assert!(d.SECTION2.a_bool);
+        d.verify().unwrap();
+    }
+
+    #[test]
+    fn ovc_test_verify() {
+        let mut d = TestConfig {
+            ..Default::default()
+        };
+        d.SECTION2.a_i32 = 50;
+        match d.verify() {
+            Ok(_) => panic!("Should not be OK"),
+            Err(e) => assert_eq!(
+                "OVConfigError: Bad [SECTION2]::a_i32. Found: 50",
+                e.to_string()
+            ),
+        }
+    }
+
+    #[test]
+    fn ovc_test_get_config() {
+        let config = r#"
+        [SECTION1]
+        a_string: i_am_a_string
+        a_vector: [1, 2, 3]
+        [SECTION2]
+        a_i32: 12
+        a_bool: true
+        "#;
+
+        let mut file = File::create("ovc_test_get_config.ini").unwrap();
+        file.write_all(config.as_bytes()).unwrap();
+        file.sync_all().unwrap();
+
+        let config = match TestConfig::get_config("ovc_test_get_config.ini") {
+            Ok(c) => {
+                std::fs::remove_file("ovc_test_get_config.ini").unwrap();
+                c
+            }
+            Err(e) => {
+                std::fs::remove_file("ovc_test_get_config.ini").unwrap();
+                panic!("{}", e);
+            }
+        };
+
+        assert_eq!(config.SECTION1.a_string, "i_am_a_string");
+        assert_eq!(config.SECTION1.a_vector, [1, 2, 3]);
+        assert_eq!(config.SECTION2.a_i32, 12);
+        assert!(config.SECTION2.a_bool);
+    }
+
+    #[test]
+    fn ovc_test_get_config_verify_failed() {
+        let config = r#"
+        [SECTION1]
+        a_string: i_am_a_string
+        a_vector: [1, 2, 3]
+        [SECTION2]
+        a_i32: 128
+        a_bool: true
+        "#;
+
+        let mut file = File::create("ovc_test_get_config_verify_failed.ini").unwrap();
+        file.write_all(config.as_bytes()).unwrap();
+        file.sync_all().unwrap();
+
+        match TestConfig::get_config("ovc_test_get_config_verify_failed.ini") {
+            Ok(_) => {
+                std::fs::remove_file("ovc_test_get_config_verify_failed.ini").unwrap();
+                panic!("Should not be OK");
+            }
+            Err(e) => {
+                std::fs::remove_file("ovc_test_get_config_verify_failed.ini").unwrap();
+                assert_eq!(
+                    "OVConfigError: Bad [SECTION2]::a_i32. Found: 128",
+                    e.to_string()
+                )
+            }
+        };
+    }
+
+    #[test]
+    fn ovc_test_get_config_no_verify() {
+        let config = r#"
+        [SECTION1]
+        a_string=i_am_a_string
+        a_vector=[1, 2, 3]
+
+        [SECTION2]
+        a_i32=128
+        a_bool=true
+        "#;
+
+        let mut file = File::create("ovc_test_get_config_no_verify.ini").unwrap();
+        file.write_all(config.as_bytes()).unwrap();
+        file.sync_all().unwrap();
+
+        let config = match TestConfig::get_config_no_verify("ovc_test_get_config_no_verify.ini") {
+            Ok(c) => {
+                std::fs::remove_file("ovc_test_get_config_no_verify.ini").unwrap();
+                c
+            }
+            Err(e) => {
+                std::fs::remove_file("ovc_test_get_config_no_verify.ini").unwrap();
+                panic!("{}", e);
+            }
+        };
+
+        assert_eq!(config.SECTION1.a_string, "i_am_a_string");
+        assert_eq!(config.SECTION1.a_vector, [1, 2, 3]);
+        assert_eq!(config.SECTION2.a_i32, 128);
+        assert!(config.SECTION2.a_bool);
+    }
+
+    #[test]
+    fn ovc_test_refresh() {
+        let config = r#"
+        [SECTION1]
+        a_string: i_am_a_string
+        a_vector: [1, 2, 3]
+        [SECTION2]
+        a_i32: 12
+        a_bool: true
+        "#;
+
+        let mut file = File::create("ovc_test_refresh.ini").unwrap();
+        file.write_all(config.as_bytes()).unwrap();
+        file.sync_all().unwrap();
+
+        let mut config = TestConfig::get_config("ovc_test_refresh.ini").unwrap();
+
+        assert_eq!(config.SECTION1.a_string, "i_am_a_string");
+        assert_eq!(config.SECTION1.a_vector, [1, 2, 3]);
+        assert_eq!(config.SECTION2.a_i32, 12);
+        assert!(config.SECTION2.a_bool);
+
+        let cfg = r#"
+        [SECTION1]
+        a_string: i_am_a_string
+        a_vector: [1, 2, 3]
+        [SECTION2]
+        a_i32: 13
+        a_bool: true
+        "#;
+
+        let mut file = File::create("ovc_test_refresh.ini").unwrap();
+        file.write_all(cfg.as_bytes()).unwrap();
+        file.sync_all().unwrap();
+
+        match config.refresh() {
+            Ok(changed) => {
+                std::fs::remove_file("ovc_test_refresh.ini").unwrap();
+                assert_eq!(changed.changed, ["SECTION2"]);
+            }
+            Err(e) => {
+                std::fs::remove_file("ovc_test_refresh.ini").unwrap();
+                panic!("{}", e);
+            }
+        };
+        assert_eq!(config.SECTION2.a_i32, 13);
+    }
+
+    #[test]
+    fn ovc_test_refresh_verify_error() {
+        let config = r#"
+        [SECTION1]
+        a_string: i_am_a_string
+        a_vector: [1, 2, 3]
+        [SECTION2]
+        a_i32: 12
+        a_bool: true
+        "#;
+
+        let mut file = File::create("ovc_test_refresh_verify_error.ini").unwrap();
+        file.write_all(config.as_bytes()).unwrap();
+        file.sync_all().unwrap();
+
+        let mut config = TestConfig::get_config("ovc_test_refresh_verify_error.ini").unwrap();
+
+        assert_eq!(config.SECTION1.a_string, "i_am_a_string");
+        assert_eq!(config.SECTION1.a_vector, [1, 2, 3]);
+        assert_eq!(config.SECTION2.a_i32, 12);
+        assert!(config.SECTION2.a_bool);
+
+        let cfg = r#"
+        [SECTION1]
+        a_string: i_am_a_string
+        a_vector: [1, 2, 3]
+        [SECTION2]
+        a_i32: 139
+        a_bool: true
+        "#;
+
+        let mut file = File::create("ovc_test_refresh_verify_error.ini").unwrap();
+        file.write_all(cfg.as_bytes()).unwrap();
+        file.sync_all().unwrap();
+
+        match config.refresh() {
+            Ok(_) => {
+                std::fs::remove_file("ovc_test_refresh_verify_error.ini").unwrap();
+                panic!("Should not be OK.");
+            }
+            Err(e) => {
+                std::fs::remove_file("ovc_test_refresh_verify_error.ini").unwrap();
+                assert_eq!(
+                    "OVConfigError: Bad [SECTION2]::a_i32. Found: 139",
+                    e.to_string()
+                );
+            }
+        };
+        assert_eq!(config.SECTION2.a_i32, 12);
+    }
+
+    #[test]
+    fn ovc_test_refresh_no_verify() {
+        let config = r#"
+        [SECTION1]
+        a_string: i_am_a_string
+        a_vector: [1, 2, 3]
+        [SECTION2]
+        a_i32: 12
+        a_bool: true
+        "#;
+
+        let mut file = File::create("ovc_test_refresh_no_verify.ini").unwrap();
+        file.write_all(config.as_bytes()).unwrap();
+        file.sync_all().unwrap();
+
+        let mut config = TestConfig::get_config("ovc_test_refresh_no_verify.ini").unwrap();
+
+        assert_eq!(config.SECTION1.a_string, "i_am_a_string");
+        assert_eq!(config.SECTION1.a_vector, [1, 2, 3]);
+        assert_eq!(config.SECTION2.a_i32, 12);
+        assert!(config.SECTION2.a_bool);
+
+        let cfg = r#"
+        [SECTION1]
+        a_string: i_am_a_string
+        a_vector: [1, 2, 3]
+        [SECTION2]
+        a_i32: 130
+        a_bool: true
+        "#;
+
+        let mut file = File::create("ovc_test_refresh_no_verify.ini").unwrap();
+        file.write_all(cfg.as_bytes()).unwrap();
+        file.sync_all().unwrap();
+
+        match config.refresh_no_verify() {
+            Ok(_) => std::fs::remove_file("ovc_test_refresh_no_verify.ini").unwrap(),
+            Err(e) => {
+                std::fs::remove_file("ovc_test_refresh_no_verify.ini").unwrap();
+                panic!("{}", e);
+            }
+        };
+        assert_eq!(config.SECTION2.a_i32, 130);
+    }
+
+    #[test]
+    fn ovc_test_flush() {
+        let mut d = TestConfig {
+            ..Default::default()
+        };
+
+        d.c_p_a_t_h = "ovc_test_flush.ini".into();
+        d.SECTION1.a_string = "C:\\dir \"q\" ; #".into();
+        d.flush().unwrap();
+        assert!(std::path::Path::new("ovc_test_flush.ini").exists());
+        let config = TestConfig::get_config("ovc_test_flush.ini").unwrap();
+        assert_eq!(d, config);
+        std::fs::remove_file("ovc_test_flush.ini").unwrap();
+
+        // Values loaded from a file or a string move along with `c_p_a_t_h`.
+        std::fs::write(
+            "ovc_test_flush_a.ini",
+            "[SECTION1]\na_string: hello\n[SECTION2]\na_i32: 12\n",
+        )
+        .unwrap();
+        let mut config = TestConfig::get_config("ovc_test_flush_a.ini").unwrap();
+        config.c_p_a_t_h = "ovc_test_flush_b.ini".into();
+        config.flush().unwrap();
+        let mut moved = TestConfig::get_config("ovc_test_flush_b.ini").unwrap();
+        std::fs::remove_file("ovc_test_flush_a.ini").unwrap();
+        std::fs::remove_file("ovc_test_flush_b.ini").unwrap();
+        moved.c_p_a_t_h = config.c_p_a_t_h.clone();
+        assert_eq!(moved, config);
+        assert_eq!(moved.SECTION1.a_string, "hello");
+
+        let mut config = TestConfig::from_str(
+            "[SECTION1]\na_string: hello\n[SECTION2]\na_i32: 12\n",
+            Format::Ini,
+        )
+        .unwrap();
+        config.c_p_a_t_h = "ovc_test_flush_c.ini".into();
+        config.flush().unwrap();
+        let flushed = TestConfig::get_config("ovc_test_flush_c.ini").unwrap();
+        std::fs::remove_file("ovc_test_flush_c.ini").unwrap();
+        assert_eq!(flushed, config);
+        assert_eq!(flushed.SECTION2.a_i32, 12);
+    }
+
+    #[test]
+    fn ovc_test_flush_failed() {
+        let mut d = TestConfig {
+            ..Default::default()
+        };
+        d.SECTION2.a_i32 = 50;
+        d.c_p_a_t_h = "ovc_test_flush_failed.ini".into();
+        match d.flush() {
+            Ok(_) => panic!("Should not be OK"),
+            Err(e) => assert_eq!(
+                "OVConfigError: Bad [SECTION2]::a_i32. Found: 50",
+                e.to_string()
+            ),
+        };
+        assert!(!std::path::Path::new("ovc_test_flush_failed.ini").exists());
     }
 
     #[test]
-    fn ovc_test_get_config() {
+    fn ovc_test_flush_no_verfiy() {
+        let mut d = TestConfig {
+            ..Default::default()
+        };
+        d.SECTION2.a_i32 = 50;
+        d.c_p_a_t_h = "ovc_test_flush_no_verfiy.ini".into();
+        d.flush_no_verify().unwrap();
+        assert!(std::path::Path::new("ovc_test_flush_no_verfiy.ini").exists());
+        let config = TestConfig::get_config_no_verify("ovc_test_flush_no_verfiy.ini").unwrap();
+        assert_eq!(d, config);
+        std::fs::remove_file("ovc_test_flush_no_verfiy.ini").unwrap();
+    }
+
+    #[test]
+    fn ovc_test_get_config_toml() {
         let config = r#"
         [SECTION1]
-        a_string: i_am_a_string
-        a_vector: [1, 2, 3]
+        a_string = "i_am_a_string"
+        a_vector = [1, 2, 3]
         [SECTION2]
-        a_i32: 12
-        a_bool: true
+        a_i32 = 12
         "#;
 
-        let mut file = File::create("ovc_test_get_config.ini").unwrap();
+        let mut file = File::create("ovc_test_get_config_toml.toml").unwrap();
         file.write_all(config.as_bytes()).unwrap();
         file.sync_all().unwrap();
 
-        let config = match TestConfig::get_config("ovc_test_get_config.ini") {
+        let config = match TestConfig::get_config("ovc_test_get_config_toml.toml") {
             Ok(c) => {
-                std::fs::remove_file("ovc_test_get_config.ini").unwrap();
+                std::fs::remove_file("ovc_test_get_config_toml.toml").unwrap();
                 c
             }
             Err(e) => {
-                std::fs::remove_file("ovc_test_get_config.ini").unwrap();
-                panic!(e);
+                std::fs::remove_file("ovc_test_get_config_toml.toml").unwrap();
+                panic!("{}", e);
             }
         };
 
+        assert_eq!(config.format(), Format::Toml);
         assert_eq!(config.SECTION1.a_string, "i_am_a_string");
         assert_eq!(config.SECTION1.a_vector, [1, 2, 3]);
         assert_eq!(config.SECTION2.a_i32, 12);
-        assert_eq!(config.SECTION2.a_bool, true);
+        assert!(config.SECTION2.a_bool);
     }
 
     #[test]
-    fn ovc_test_get_config_verify_failed() {
+    fn ovc_test_get_config_with_format() {
+        let config = r#"
+        [SECTION2]
+        a_i32 = 12
+        a_bool = false
+        "#;
+
+        let mut file = File::create("ovc_test_get_config_with_format.conf").unwrap();
+        file.write_all(config.as_bytes()).unwrap();
+        file.sync_all().unwrap();
+
+        let config = TestConfig::get_config_with_format(
+            "ovc_test_get_config_with_format.conf",
+            Format::Toml,
+        );
+        std::fs::remove_file("ovc_test_get_config_with_format.conf").unwrap();
+        let config = config.unwrap();
+
+        assert_eq!(config.format(), Format::Toml);
+        assert_eq!(config.SECTION2.a_i32, 12);
+        assert!(!config.SECTION2.a_bool);
+    }
+
+    #[test]
+    fn ovc_test_flush_toml() {
+        let mut d = TestConfig {
+            ..Default::default()
+        };
+
+        d.c_p_a_t_h = "ovc_test_flush_toml.toml".into();
+        d.SECTION1.a_string = "with \"quotes\"".into();
+        d.flush().unwrap();
+        let config = TestConfig::get_config("ovc_test_flush_toml.toml").unwrap();
+        std::fs::remove_file("ovc_test_flush_toml.toml").unwrap();
+        assert_eq!(d, config);
+    }
+
+    #[test]
+    fn ovc_test_refresh_keeps_old_on_error() {
+        let config = r#"
+        [SECTION1]
+        a_string: i_am_a_string
+        [SECTION2]
+        a_i32: 12
+        "#;
+
+        let mut file = File::create("ovc_test_refresh_keeps_old_on_error.ini").unwrap();
+        file.write_all(config.as_bytes()).unwrap();
+        file.sync_all().unwrap();
+
+        let mut config = TestConfig::get_config("ovc_test_refresh_keeps_old_on_error.ini").unwrap();
+
+        let cfg = r#"
+        [SECTION1]
+        a_string: i_am_a_new_string
+        [SECTION2]
+        a_i32: not_a_number
+        "#;
+
+        let mut file = File::create("ovc_test_refresh_keeps_old_on_error.ini").unwrap();
+        file.write_all(cfg.as_bytes()).unwrap();
+        file.sync_all().unwrap();
+
+        let res = config.refresh_no_verify();
+        std::fs::remove_file("ovc_test_refresh_keeps_old_on_error.ini").unwrap();
+        assert!(res.is_err());
+        assert_eq!(config.SECTION1.a_string, "i_am_a_string");
+        assert_eq!(config.SECTION2.a_i32, 12);
+    }
+
+    #[test]
+    fn ovc_test_from_ini() {
+        let ini = Ini::load_from_str(
+            r#"
+        [SECTION1]
+        a_string: i_am_a_string
+        [SECTION2]
+        a_i32: 12
+        "#,
+        )
+        .unwrap();
+
+        let s1 = ovconfig::SECTION1::from_ini(&ini).unwrap();
+        let s2 = ovconfig::SECTION2::from_ini(&ini).unwrap();
+        assert_eq!(s1.a_string, "i_am_a_string");
+        assert_eq!(s1.a_vector, [1, 2, 3]);
+        assert_eq!(s2.a_i32, 12);
+
+        let config = TestConfig::from_document(&Document::Ini(ini)).unwrap();
+        assert_eq!(config.SECTION1, s1);
+        assert_eq!(config.SECTION2, s2);
+    }
+
+    #[test]
+    fn ovc_test_from_str() {
         let config = r#"
         [SECTION1]
         a_string: i_am_a_string
+        [SECTION2]
+        a_i32: 12
+        "#;
+
+        let config = TestConfig::from_str(config, Format::Ini).unwrap();
+        assert_eq!(config.SECTION1.a_string, "i_am_a_string");
+        assert_eq!(config.SECTION2.a_i32, 12);
+
+        match TestConfig::from_str("[SECTION2]\na_i32 = 128", Format::Toml) {
+            Ok(_) => panic!("Should not be OK"),
+            Err(e) => assert_eq!(
+                "OVConfigError: Bad [SECTION2]::a_i32. Found: 128",
+                e.to_string()
+            ),
+        }
+        let config =
+            TestConfig::from_str_no_verify("[SECTION2]\na_i32 = 128", Format::Toml).unwrap();
+        assert_eq!(config.SECTION2.a_i32, 128);
+    }
+
+    #[test]
+    fn ovc_test_reader_writer() {
+        let mut d = TestConfig::from_str("", Format::Toml).unwrap();
+        d.SECTION2.a_i32 = 18;
+
+        let mut buf = Vec::new();
+        d.write_to(&mut buf).unwrap();
+        let config = TestConfig::from_reader(buf.as_slice(), Format::Toml).unwrap();
+        assert_eq!(d, config);
+        assert_eq!(
+            TestConfig::default(),
+            TestConfig::from_str("", Format::Ini).unwrap()
+        );
+
+        let s = d.SECTION2.to_string(Format::Ini).unwrap();
+        let section = ovconfig::SECTION2::from_reader(s.as_bytes(), Format::Ini).unwrap();
+        assert_eq!(d.SECTION2, section);
+    }
+
+    #[test]
+    fn ovc_test_verify_all() {
+        let mut d = TestConfig {
+            ..Default::default()
+        };
+        d.verify_all().unwrap();
+
+        d.SECTION1.a_string = "".into();
+        d.SECTION1.a_vector = vec![1, 2, 3, 4];
+        d.SECTION2.a_i32 = 50;
+        match d.verify_all() {
+            Ok(_) => panic!("Should not be OK"),
+            Err(OVConfigError::Multiple(errors)) => assert_eq!(errors.len(), 3),
+            Err(e) => panic!("{}", e),
+        }
+        assert_eq!(
+            "MultipleErrors: 3 error(s)\n\
+             OVConfigError: Bad [SECTION1]::a_string. Found: \"\"\n\
+             OVConfigError: Bad [SECTION1]::a_vector. Found: [1,2,3,4]\n\
+             OVConfigError: Bad [SECTION2]::a_i32. Found: 50",
+            d.verify_all().unwrap_err().to_string()
+        );
+        assert!(d.SECTION2.verify_all().is_err());
+    }
+
+    #[test]
+    fn ovc_test_parse_value_error() {
+        let config = r#"
+        [SECTION1]
         a_vector: [1, 2, 3]
         [SECTION2]
-        a_i32: 128
         a_bool: true
+        a_i32: not_a_number
         "#;
 
-        let mut file = File::create("ovc_test_get_config_verify_failed.ini").unwrap();
+        match TestConfig::from_str(config, Format::Ini) {
+            Ok(_) => panic!("Should not be OK"),
+            Err(OVConfigError::ParseValueError {
+                section,
+                key,
+                value,
+                expected,
+                line,
+                ..
+            }) => {
+                assert_eq!(section, "SECTION2");
+                assert_eq!(key, "a_i32");
+                assert_eq!(value, "not_a_number");
+                assert_eq!(expected, "i32");
+                assert_eq!(line, Some(6));
+            }
+            Err(e) => panic!("{}", e),
+        }
+
+        let config = "[SECTION1]\na_vector = [1, \"2\"]\n";
+        let e = TestConfig::from_str(config, Format::Toml).unwrap_err();
+        assert!(e.to_string().starts_with(
+            "ParseValueError: Bad [SECTION1]::a_vector at line 2. Expected: Vec<i32>."
+        ));
+    }
+
+    #[test]
+    fn ovc_test_flush_preserving() {
+        let config = r#"; Managed by hand
+[SECTION1]
+# the name
+a_string = i_am_a_string ; inline comment
+unknown_key = 1
+
+[FOREIGN]
+foo = bar
+
+[SECTION2]
+a_i32: 12
+"#;
+
+        let mut file = File::create("ovc_test_flush_preserving.ini").unwrap();
         file.write_all(config.as_bytes()).unwrap();
         file.sync_all().unwrap();
 
-        match TestConfig::get_config("ovc_test_get_config_verify_failed.ini") {
-            Ok(_) => {
-                std::fs::remove_file("ovc_test_get_config_verify_failed.ini").unwrap();
-                panic!("Should not be OK");
+        let mut d = TestConfig::get_config("ovc_test_flush_preserving.ini").unwrap();
+        d.SECTION2.a_i32 = 18;
+        d.flush_preserving().unwrap();
+
+        let text = std::fs::read_to_string("ovc_test_flush_preserving.ini").unwrap();
+        let config = TestConfig::get_config("ovc_test_flush_preserving.ini").unwrap();
+        std::fs::remove_file("ovc_test_flush_preserving.ini").unwrap();
+        assert_eq!(d, config);
+        assert_eq!(
+            text,
+            r#"; Managed by hand
+[SECTION1]
+# the name
+a_string = i_am_a_string ; inline comment
+unknown_key = 1
+a_vector=[1,2,3]
+
+[FOREIGN]
+foo = bar
+
+[SECTION2]
+a_i32: 18
+a_bool=true
+"#
+        );
+    }
+
+    #[test]
+    fn ovc_test_flush_preserving_toml() {
+        let config = r#"[SECTION1]
+a_vector = [
+    1,
+    2,
+] # trailing
+a_string = "keep"
+"#;
+
+        let mut file = File::create("ovc_test_flush_preserving_toml.toml").unwrap();
+        file.write_all(config.as_bytes()).unwrap();
+        file.sync_all().unwrap();
+
+        let mut d = TestConfig::get_config("ovc_test_flush_preserving_toml.toml").unwrap();
+        d.SECTION1.a_vector = vec![3];
+        d.flush_preserving().unwrap();
+
+        let text = std::fs::read_to_string("ovc_test_flush_preserving_toml.toml").unwrap();
+        let config = TestConfig::get_config("ovc_test_flush_preserving_toml.toml").unwrap();
+        std::fs::remove_file("ovc_test_flush_preserving_toml.toml").unwrap();
+        assert_eq!(d, config);
+        assert_eq!(
+            text,
+            r#"[SECTION1]
+a_vector = [3] # trailing
+a_string = "keep"
+
+[SECTION2]
+a_i32 = 15
+a_bool = true
+"#
+        );
+    }
+
+    #[test]
+    fn ovc_test_flush_preserving_per_key() {
+        let path = "ovc_test_flush_preserving_per_key.ini";
+        // a_i32 cannot be parsed, a_string must not be rewritten because of it.
+        std::fs::write(
+            path,
+            "[SECTION1]\na_string = kept ; comment\n[SECTION2]\na_i32: x\n",
+        )
+        .unwrap();
+        let mut d = TestConfig {
+            c_p_a_t_h: path.into(),
+            ..Default::default()
+        };
+        d.SECTION1.a_string = "kept".into();
+        d.flush_preserving().unwrap();
+        let text = std::fs::read_to_string(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert!(text.contains("a_string = kept ; comment\n"));
+        assert!(text.contains("a_i32: 15\n"));
+
+        let path = "ovc_test_flush_preserving_inline.toml";
+        std::fs::write(path, "SECTION1 = { a_string = \"a\" }\n").unwrap();
+        let d = TestConfig::get_config(path).unwrap();
+        let res = d.flush_preserving();
+        let text = std::fs::read_to_string(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        match res {
+            Err(OVConfigError::InlineTable { section }) => assert_eq!(section, "SECTION1"),
+            r => panic!("{:?}", r),
+        }
+        assert_eq!(text, "SECTION1 = { a_string = \"a\" }\n");
+
+        // Lines of a multi-line value that start with `[` are not section headers.
+        let path = "ovc_test_flush_preserving_nested.toml";
+        std::fs::write(
+            path,
+            "[SECTION1]\nm = [\n  [1, 2],\n  [3],\n]\n\n[SECTION2]\na_i32 = 12\n",
+        )
+        .unwrap();
+        let mut d = TestConfig::get_config(path).unwrap();
+        d.SECTION1.a_vector = vec![9];
+        d.flush_preserving().unwrap();
+        let text = std::fs::read_to_string(path).unwrap();
+        let config = TestConfig::get_config(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(d, config);
+        assert!(text.starts_with("[SECTION1]\nm = [\n  [1, 2],\n  [3],\n]\n"));
+
+        // A trailing comment after a section header.
+        let path = "ovc_test_flush_preserving_header.ini";
+        std::fs::write(path, "[SECTION2] ; main section\na_i32: 12\n").unwrap();
+        let mut d = TestConfig::get_config(path).unwrap();
+        d.SECTION2.a_bool = false;
+        d.flush_preserving().unwrap();
+        let text = std::fs::read_to_string(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(text.matches("[SECTION2]").count(), 1);
+        assert!(text.contains("[SECTION2] ; main section\na_i32: 12\na_bool=false\n"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn ovc_test_flush_atomic() {
+        use std::os::unix::fs::PermissionsExt;
+
+        std::fs::create_dir_all("ovc_test_flush_atomic").unwrap();
+        let path = "ovc_test_flush_atomic/config.ini";
+        File::create(path).unwrap();
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o640)).unwrap();
+
+        let mut d = TestConfig {
+            ..Default::default()
+        };
+        d.c_p_a_t_h = path.into();
+        d.SECTION2.a_i32 = 18;
+        d.flush().unwrap();
+
+        let mode = std::fs::metadata(path).unwrap().permissions().mode();
+        let entries = std::fs::read_dir("ovc_test_flush_atomic").unwrap().count();
+        let config = TestConfig::get_config(path).unwrap();
+        std::fs::remove_dir_all("ovc_test_flush_atomic").unwrap();
+        assert_eq!(mode & 0o777, 0o640);
+        assert_eq!(entries, 1);
+        assert_eq!(d, config);
+
+        // In place, the file itself is rewritten.
+        use std::os::unix::fs::MetadataExt;
+        let path = "ovc_test_flush_in_place.ini";
+        std::fs::write(path, "[SECTION2]\na_i32: 12\n").unwrap();
+        let inode = std::fs::metadata(path).unwrap().ino();
+        let mut d = TestConfig::get_config(path).unwrap().with_in_place_writes();
+        d.SECTION2.a_i32 = 18;
+        d.flush().unwrap();
+        d.refresh().unwrap();
+        let same = std::fs::metadata(path).unwrap().ino() == inode;
+        std::fs::remove_file(path).unwrap();
+        assert!(same);
+        assert_eq!(d.SECTION2.a_i32, 18);
+        assert!(d.m_e_t_a.in_place);
+    }
+
+    #[test]
+    fn ovc_test_flush_atomic_failed() {
+        let mut d = TestConfig {
+            ..Default::default()
+        };
+        d.c_p_a_t_h = "ovc_test_flush_atomic_failed/config.ini".into();
+        match d.flush() {
+            Ok(_) => panic!("Should not be OK"),
+            Err(e) => assert!(e
+                .to_string()
+                .starts_with("IoError: create temporary file failed")),
+        }
+    }
+
+    #[cfg(feature = "watch")]
+    #[test]
+    fn ovc_test_watch() {
+        std::fs::create_dir_all("ovc_test_watch").unwrap();
+        let path = "ovc_test_watch/config.ini";
+        std::fs::write(path, "[SECTION2]\na_i32: 12\n").unwrap();
+
+        let config = TestConfig::get_config(path).unwrap();
+        let watcher = Watcher::new(config, std::time::Duration::from_millis(100)).unwrap();
+        let (tx, rx) = std::sync::mpsc::channel();
+        watcher.on_change(move |old: &TestConfig, new: &TestConfig| {
+            tx.send((old.SECTION2.a_i32, new.SECTION2.a_i32)).unwrap();
+        });
+
+        // Save the way editors do: write a new file and rename it over the old one.
+        std::fs::write("ovc_test_watch/config.ini.new", "[SECTION2]\na_i32: 13\n").unwrap();
+        std::fs::rename("ovc_test_watch/config.ini.new", path).unwrap();
+        let changed = rx.recv_timeout(std::time::Duration::from_secs(10));
+        let current = watcher.config().SECTION2.a_i32;
+
+        drop(watcher);
+        std::fs::remove_dir_all("ovc_test_watch").unwrap();
+        assert_eq!(changed.unwrap(), (12, 13));
+        assert_eq!(current, 13);
+    }
+
+    #[test]
+    fn ovc_test_shared() {
+        std::fs::write("ovc_test_shared.ini", "[SECTION2]\na_i32: 12\n").unwrap();
+        let shared = std::sync::Arc::new(
+            TestConfig::get_config("ovc_test_shared.ini")
+                .unwrap()
+                .into_shared(),
+        );
+        let (tx, rx) = std::sync::mpsc::channel();
+        let tx = std::sync::Mutex::new(tx);
+        shared.subscribe(move |old: &TestConfig, new: &TestConfig| {
+            let tx = tx.lock().unwrap();
+            tx.send((old.SECTION2.a_i32, new.SECTION2.a_i32)).unwrap();
+        });
+
+        let snapshot = shared.load();
+        let reader = {
+            let shared = shared.clone();
+            std::thread::spawn(move || shared.load().SECTION2.a_i32)
+        };
+        assert!([12, 13].contains(&reader.join().unwrap()));
+
+        std::fs::write("ovc_test_shared.ini", "[SECTION2]\na_i32: 13\n").unwrap();
+        let changed = shared.reload();
+        std::fs::write("ovc_test_shared.ini", "[SECTION2]\na_i32: 130\n").unwrap();
+        let failed = shared.reload();
+        std::fs::remove_file("ovc_test_shared.ini").unwrap();
+
+        assert!(changed.unwrap());
+        assert!(failed.is_err());
+        assert_eq!(snapshot.SECTION2.a_i32, 12);
+        assert_eq!(shared.load().SECTION2.a_i32, 13);
+        assert_eq!(rx.try_recv().unwrap(), (12, 13));
+        assert!(rx.try_recv().is_err());
+
+        shared.subscribe(|_: &TestConfig, _: &TestConfig| panic!("listener failed"));
+        std::fs::write("ovc_test_shared.ini", "[SECTION2]\na_i32: 14\n").unwrap();
+        let first = shared.reload();
+        std::fs::write("ovc_test_shared.ini", "[SECTION2]\na_i32: 15\n").unwrap();
+        let second = shared.reload();
+        std::fs::remove_file("ovc_test_shared.ini").unwrap();
+
+        assert!(first.unwrap());
+        assert!(second.unwrap());
+        assert_eq!(shared.load().SECTION2.a_i32, 15);
+        assert_eq!(rx.try_recv().unwrap(), (13, 14));
+        assert_eq!(rx.try_recv().unwrap(), (14, 15));
+    }
+
+    #[test]
+    fn ovc_test_env() {
+        assert_eq!(
+            value::env_name("OVD", "SECTION1", "a_i32"),
+            "OVD_SECTION1_A_I32"
+        );
+        assert_eq!(
+            value::env_name("OVD_", "SECTION1", "a_i32"),
+            "OVD_SECTION1_A_I32"
+        );
+
+        std::fs::write("ovc_test_env.ini", "[SECTION2]\na_i32: 12\n").unwrap();
+        std::env::set_var("OVCTESTENV_SECTION2_A_I32", "13");
+        std::env::set_var("OVCTESTENV_SECTION1_A_VECTOR", "[4, 5]");
+        let mut config = TestConfig::get_config_with_env("ovc_test_env.ini", "OVCTESTENV").unwrap();
+        assert_eq!(config.SECTION2.a_i32, 13);
+        assert_eq!(config.SECTION1.a_vector, [4, 5]);
+        assert_eq!(
+            config.env_overrides(),
+            [
+                (
+                    "SECTION1",
+                    "a_vector",
+                    "OVCTESTENV_SECTION1_A_VECTOR".to_string()
+                ),
+                ("SECTION2", "a_i32", "OVCTESTENV_SECTION2_A_I32".to_string()),
+            ]
+        );
+        // Only variables applied when loading count, and layered env sources are tracked as well.
+        std::env::set_var("OVCTESTENV_SECTION2_A_BOOL", "false");
+        assert_eq!(config.env_overrides().len(), 2);
+        std::env::remove_var("OVCTESTENV_SECTION2_A_BOOL");
+        let layered = TestConfig::layered()
+            .file("ovc_test_env.ini")
+            .env("OVCTESTENV")
+            .build()
+            .unwrap();
+        assert_eq!(layered.env_overrides(), config.env_overrides());
+
+        std::fs::write("ovc_test_env.ini", "[SECTION2]\na_i32: 14\na_bool: false\n").unwrap();
+        let changed = config.refresh().unwrap();
+        assert_eq!(changed.changed, ["SECTION2"]);
+        assert_eq!(config.SECTION2.a_i32, 13);
+        assert!(!config.SECTION2.a_bool);
+
+        // Values from the environment are not written to the file.
+        config.SECTION2.a_bool = true;
+        config.flush().unwrap();
+        let flushed = TestConfig::get_config("ovc_test_env.ini").unwrap();
+        assert_eq!(flushed.SECTION2.a_i32, 14);
+        assert!(flushed.SECTION2.a_bool);
+        assert_eq!(flushed.SECTION1.a_vector, [1, 2, 3]);
+        assert!(!std::fs::read_to_string("ovc_test_env.ini")
+            .unwrap()
+            .contains("a_vector"));
+        config.set("SECTION2", "a_i32", "16").unwrap();
+        config.flush_preserving().unwrap();
+        let flushed = TestConfig::get_config("ovc_test_env.ini").unwrap();
+        assert_eq!(flushed.SECTION2.a_i32, 16);
+        assert_eq!(flushed.SECTION1.a_vector, [1, 2, 3]);
+
+        std::env::set_var("OVCTESTENV_SECTION2_A_I32", "130");
+        let too_big = TestConfig::get_config_with_env("ovc_test_env.ini", "OVCTESTENV");
+        std::env::set_var("OVCTESTENV_SECTION2_A_I32", "x");
+        let not_int = TestConfig::get_config_with_env("ovc_test_env.ini", "OVCTESTENV");
+        std::env::remove_var("OVCTESTENV_SECTION2_A_I32");
+        std::env::remove_var("OVCTESTENV_SECTION1_A_VECTOR");
+        std::fs::remove_file("ovc_test_env.ini").unwrap();
+
+        match too_big {
+            Err(OVConfigError::BadValue { key, .. }) => assert_eq!(key, "a_i32"),
+            r => panic!("{:?}", r),
+        }
+        match not_int {
+            Err(OVConfigError::ParseValueError { key, expected, .. }) => {
+                assert_eq!(key, "a_i32");
+                assert_eq!(expected, "i32");
             }
-            Err(e) => {
-                std::fs::remove_file("ovc_test_get_config_verify_failed.ini").unwrap();
-                assert_eq!(
-                    "OVConfigError: Bad [SECTION2]::a_i32. Found: 128",
-                    e.to_string()
+            r => panic!("{:?}", r),
+        }
+    }
+
+    #[test]
+    fn ovc_test_overrides() {
+        std::fs::write("ovc_test_overrides.ini", "[SECTION2]\na_i32: 12\n").unwrap();
+        let mut config = TestConfig::get_config("ovc_test_overrides.ini")
+            .unwrap()
+            .with_overrides(vec![
+                "SECTION2.a_i32=18",
+                "SECTION1.a_string = from cli",
+                "SECTION1.a_vector=[7]",
+            ])
+            .unwrap();
+        assert_eq!(config.SECTION2.a_i32, 18);
+        assert_eq!(config.SECTION1.a_string, "from cli");
+        assert_eq!(config.SECTION1.a_vector, [7]);
+
+        std::fs::write(
+            "ovc_test_overrides.ini",
+            "[SECTION2]\na_i32: 13\na_bool: false\n",
+        )
+        .unwrap();
+        config.refresh().unwrap();
+        std::fs::remove_file("ovc_test_overrides.ini").unwrap();
+        assert_eq!(config.SECTION2.a_i32, 18);
+        assert!(!config.SECTION2.a_bool);
+
+        let errors = [
+            "SECTION3.a_i32=1",
+            "SECTION2.a_u32=1",
+            "SECTION2.a_i32=x",
+            "SECTION2.a_i32",
+            "a_i32=1",
+        ]
+        .iter()
+        .map(|arg| {
+            TestConfig::default()
+                .with_overrides(Some(*arg))
+                .unwrap_err()
+        })
+        .collect::<Vec<_>>();
+        match &errors[..] {
+            [OVConfigError::UnknownSection { .. }, OVConfigError::UnknownKey { .. }, OVConfigError::ParseValueError { .. }, OVConfigError::BadOverride { .. }, OVConfigError::BadOverride { .. }] =>
+                {}
+            e => panic!("{:?}", e),
+        }
+
+        let schema = TestConfig::schema();
+        assert_eq!(schema.len(), 4);
+        assert_eq!(
+            schema[0],
+            KeyInfo {
+                section: "SECTION1",
+                key: "a_string",
+                type_name: "String",
+                default: Some("key1".into()),
+                description: "Must not be empty.".into(),
+                restart: false,
+            }
+        );
+        assert_eq!(schema[1].default.as_deref(), Some("[1,2,3]"));
+    }
+
+    #[cfg(feature = "clap")]
+    #[test]
+    fn ovc_test_overrides_clap() {
+        let schema = TestConfig::schema();
+        let cmd = clap::Command::new("test").args(cli::args(&schema));
+        let matches = cmd
+            .try_get_matches_from(vec![
+                "test",
+                "--SECTION2.a_i32",
+                "17",
+                "--set",
+                "SECTION2.a_i32=18",
+                "--set",
+                "SECTION2.a_bool=false",
+            ])
+            .unwrap();
+        let overrides = cli::overrides(&matches, &schema);
+        assert_eq!(
+            overrides,
+            [
+                "SECTION2.a_i32=17",
+                "SECTION2.a_i32=18",
+                "SECTION2.a_bool=false"
+            ]
+        );
+        let config = TestConfig::default().with_overrides(overrides).unwrap();
+        assert_eq!(config.SECTION2.a_i32, 18);
+        assert!(!config.SECTION2.a_bool);
+        assert_eq!(config.SECTION1.a_string, "key1");
+    }
+
+    #[test]
+    fn ovc_test_layers() {
+        let dir = "ovc_test_layers";
+        std::fs::create_dir_all(format!("{}/conf.d", dir)).unwrap();
+        std::fs::write(
+            format!("{}/system.ini", dir),
+            "[SECTION1]\na_string: system\n[SECTION2]\na_i32: 100\na_bool: false\n",
+        )
+        .unwrap();
+        std::fs::write(format!("{}/user.toml", dir), "[SECTION1]\na_vector = [9]\n").unwrap();
+        std::fs::write(
+            format!("{}/conf.d/20-b.conf", dir),
+            "[SECTION2]\na_i32: 2\n",
+        )
+        .unwrap();
+        std::fs::write(
+            format!("{}/conf.d/10-a.conf", dir),
+            "[SECTION2]\na_i32: 1\n",
+        )
+        .unwrap();
+        std::fs::write(
+            format!("{}/conf.d/.hidden.conf", dir),
+            "[SECTION2]\na_i32: 3\n",
+        )
+        .unwrap();
+        std::env::set_var("OVCTESTLAYERS_SECTION1_A_STRING", "env");
+
+        let layers = TestConfig::layered()
+            .file(&format!("{}/system.ini", dir))
+            .optional_file(&format!("{}/missing.ini", dir))
+            .optional_file(&format!("{}/user.toml", dir))
+            .drop_in_dir(&format!("{}/conf.d", dir))
+            .env("OVCTESTLAYERS");
+        assert_eq!(layers.sources().len(), 5);
+        let mut config = layers.clone().build().unwrap();
+        assert_eq!(config.SECTION1.a_string, "env");
+        assert_eq!(config.SECTION1.a_vector, [9]);
+        assert_eq!(config.SECTION2.a_i32, 2);
+        assert!(!config.SECTION2.a_bool);
+        assert_eq!(config.c_p_a_t_h, format!("{}/user.toml", dir));
+
+        // Only verified once every layer is applied.
+        std::fs::remove_dir_all(format!("{}/conf.d", dir)).unwrap();
+        assert!(layers.clone().build().is_err());
+        let mut config2 = layers.cli(vec!["SECTION2.a_i32=5"]).build().unwrap();
+        assert_eq!(config2.SECTION2.a_i32, 5);
+
+        // Flush only writes the keys of the last file and the ones set by the program.
+        config2.set("SECTION2", "a_bool", "true").unwrap();
+        config2.flush().unwrap();
+        let user = std::fs::read_to_string(format!("{}/user.toml", dir)).unwrap();
+        let user = Toml::load_from_str(&user).unwrap();
+        assert_eq!(
+            user.get("SECTION1", "a_vector"),
+            Some(&toml::Value::Array(vec![9.into()]))
+        );
+        assert_eq!(
+            user.get("SECTION2", "a_bool"),
+            Some(&toml::Value::Boolean(true))
+        );
+        assert_eq!(user.get("SECTION1", "a_string"), None);
+        assert_eq!(user.get("SECTION2", "a_i32"), None);
+        let system = TestConfig::from_sources_no_verify(
+            TestConfig::layered()
+                .file(&format!("{}/system.ini", dir))
+                .optional_file(&format!("{}/missing.ini", dir))
+                .sources()
+                .to_vec(),
+        )
+        .unwrap();
+        assert_eq!(system.c_p_a_t_h, format!("{}/system.ini", dir));
+
+        std::fs::write(
+            format!("{}/missing.ini", dir),
+            "[SECTION2]\na_bool: true\na_i32: 4\n",
+        )
+        .unwrap();
+        let changed = config.refresh().unwrap();
+        std::env::remove_var("OVCTESTLAYERS_SECTION1_A_STRING");
+        let required = TestConfig::layered()
+            .file(&format!("{}/none.ini", dir))
+            .build();
+        std::fs::remove_dir_all(dir).unwrap();
+
+        assert_eq!(changed.changed, ["SECTION2"]);
+        assert_eq!(config.SECTION2.a_i32, 4);
+        assert!(config.SECTION2.a_bool);
+        assert_eq!(config.SECTION1.a_string, "env");
+        match required {
+            Err(OVConfigError::IoError(_)) => {}
+            r => panic!("{:?}", r),
+        }
+    }
+
+    #[test]
+    fn ovc_test_drop_ins() {
+        let dir = "ovc_test_drop_ins";
+        let main = format!("{}/service.conf", dir);
+        let drop_ins = format!("{}/service.conf.d", dir);
+        std::fs::create_dir_all(&drop_ins).unwrap();
+        std::fs::write(&main, "[SECTION1]\na_string: main\n[SECTION2]\na_i32: 12\n").unwrap();
+        std::fs::write(format!("{}/10-a.conf", drop_ins), "[SECTION2]\na_i32: 13\n").unwrap();
+        std::fs::write(
+            format!("{}/20-b.conf", drop_ins),
+            "# keep me\n[SECTION2]\na_bool: false\n",
+        )
+        .unwrap();
+
+        let mut config = TestConfig::get_config_with_drop_ins(&main, &drop_ins).unwrap();
+        assert_eq!(config.SECTION1.a_string, "main");
+        assert_eq!(config.SECTION2.a_i32, 13);
+        assert!(!config.SECTION2.a_bool);
+
+        config.SECTION2.a_i32 = 18;
+        config
+            .flush_drop_in(&drop_ins, "20-b.conf", "SECTION2", "a_i32")
+            .unwrap();
+        config
+            .flush_drop_in(&drop_ins, "50-new.conf", "SECTION1", "a_string")
+            .unwrap();
+        let unknown = config.flush_drop_in(&drop_ins, "50-new.conf", "SECTION1", "a_i32");
+        config.SECTION2.a_i32 = 30;
+        let bad = config.flush_drop_in(&drop_ins, "50-new.conf", "SECTION2", "a_i32");
+
+        let fragment = std::fs::read_to_string(format!("{}/20-b.conf", drop_ins)).unwrap();
+        let created = std::fs::read_to_string(format!("{}/50-new.conf", drop_ins)).unwrap();
+        let main_text = std::fs::read_to_string(&main).unwrap();
+        config.refresh().unwrap();
+        std::fs::remove_dir_all(dir).unwrap();
+
+        assert_eq!(fragment, "# keep me\n[SECTION2]\na_bool: false\na_i32=18\n");
+        assert_eq!(created, "[SECTION1]\na_string=\"main\"\n");
+        assert_eq!(
+            main_text,
+            "[SECTION1]\na_string: main\n[SECTION2]\na_i32: 12\n"
+        );
+        assert_eq!(config.SECTION2.a_i32, 18);
+        assert_eq!(config.SECTION1.a_string, "main");
+        match unknown {
+            Err(OVConfigError::UnknownKey { .. }) => {}
+            r => panic!("{:?}", r),
+        }
+        match bad {
+            Err(OVConfigError::BadValue { .. }) => {}
+            r => panic!("{:?}", r),
+        }
+    }
+
+    #[test]
+    fn ovc_test_include() {
+        let dir = "ovc_test_include";
+        std::fs::create_dir_all(format!("{}/sub", dir)).unwrap();
+        let write =
+            |name: &str, text: &str| std::fs::write(format!("{}/{}", dir, name), text).unwrap();
+        write(
+            "main.ini",
+            "@include = common.ini\n@include = flag.ini\n[include]\nextra = sub/extra.toml\n[SECTION2]\na_i32: 12\n",
+        );
+        write(
+            "common.ini",
+            "[SECTION1]\na_string: common\n[SECTION2]\na_i32: 11\n",
+        );
+        write(
+            "sub/extra.toml",
+            "\"@include\" = [\"../vector.ini\"]\n[SECTION2]\na_bool = false\n",
+        );
+        write("vector.ini", "[SECTION1]\na_vector: [5]\n");
+        write("flag.ini", "[SECTION2]\na_bool: false\n");
+
+        let mut config = TestConfig::get_config(&format!("{}/main.ini", dir)).unwrap();
+        assert_eq!(config.SECTION1.a_string, "common");
+        assert_eq!(config.SECTION1.a_vector, [5]);
+        assert_eq!(config.SECTION2.a_i32, 12);
+        assert!(!config.SECTION2.a_bool);
+
+        // Flush keeps the includes, and keys from included files stay there.
+        config.SECTION2.a_i32 = 13;
+        config.flush().unwrap();
+        let text = std::fs::read_to_string(format!("{}/main.ini", dir)).unwrap();
+        assert!(text.starts_with("@include=common.ini\n@include=flag.ini\n"));
+        assert!(text.contains("[include]\nextra=sub/extra.toml\n"));
+        assert!(!text.contains("a_string") && !text.contains("a_bool"));
+        config.flush_preserving().unwrap();
+        let text = std::fs::read_to_string(format!("{}/main.ini", dir)).unwrap();
+        assert!(!text.contains("a_string") && !text.contains("a_bool"));
+        write(
+            "common.ini",
+            "[SECTION1]\na_string: edited\n[SECTION2]\na_i32: 11\n",
+        );
+        let config = TestConfig::get_config(&format!("{}/main.ini", dir)).unwrap();
+        assert_eq!(config.SECTION1.a_string, "edited");
+        assert_eq!(config.SECTION2.a_i32, 13);
+        assert!(!config.SECTION2.a_bool);
+
+        write("vector.ini", "[SECTION1]\na_vector: [5\n");
+        let bad_value = TestConfig::get_config(&format!("{}/main.ini", dir));
+        write("vector.ini", "@include = sub/extra.toml\n");
+        let cycle = TestConfig::get_config(&format!("{}/main.ini", dir));
+        write("vector.ini", "");
+        write("common.ini", "@include = common.ini\n");
+        let self_cycle = TestConfig::get_config(&format!("{}/main.ini", dir));
+        for i in 0..=include::MAX_DEPTH {
+            write(
+                &format!("deep{}.ini", i),
+                &format!("@include = deep{}.ini\n", i + 1),
+            );
+        }
+        write(&format!("deep{}.ini", include::MAX_DEPTH + 1), "");
+        let too_deep = TestConfig::get_config(&format!("{}/deep0.ini", dir));
+        write("common.ini", "@include = missing.ini\n");
+        let missing = TestConfig::get_config(&format!("{}/main.ini", dir));
+        std::fs::remove_dir_all(dir).unwrap();
+
+        let main = format!("{}/main.ini", dir);
+        let extra = format!("{}/sub/extra.toml", dir);
+        let vector = format!("{}/sub/../vector.ini", dir);
+        match bad_value {
+            Err(OVConfigError::Include { chain, error }) => {
+                assert_eq!(chain, [main.clone(), extra.clone(), vector.clone()]);
+                match *error {
+                    OVConfigError::ParseValueError { line, .. } => assert_eq!(line, Some(2)),
+                    e => panic!("{:?}", e),
+                }
+            }
+            r => panic!("{:?}", r),
+        }
+        match cycle {
+            Err(OVConfigError::BadInclude { chain, .. }) => {
+                assert_eq!(chain.len(), 4);
+                assert_eq!(chain[0], main);
+            }
+            r => panic!("{:?}", r),
+        }
+        match self_cycle {
+            Err(e @ OVConfigError::BadInclude { .. }) => assert_eq!(
+                e.to_string(),
+                format!(
+                    "BadInclude: {} -> {}/common.ini -> {}/common.ini: include cycle",
+                    main, dir, dir
                 )
+            ),
+            r => panic!("{:?}", r),
+        }
+        match too_deep {
+            Err(OVConfigError::BadInclude { chain, .. }) => {
+                assert_eq!(chain.len(), include::MAX_DEPTH + 2)
             }
-        };
+            r => panic!("{:?}", r),
+        }
+        match missing {
+            Err(OVConfigError::Include { error, .. }) => match *error {
+                OVConfigError::IoError(_) => {}
+                e => panic!("{:?}", e),
+            },
+            r => panic!("{:?}", r),
+        }
     }
 
     #[test]
-    fn ovc_test_get_config_no_verify() {
-        let config = r#"
-        [SECTION1]
-        a_string=i_am_a_string
-        a_vector=[1, 2, 3]
-
-        [SECTION2]
-        a_i32=128
-        a_bool=true
-        "#;
-
-        let mut file = File::create("ovc_test_get_config_no_verify.ini").unwrap();
-        file.write_all(config.as_bytes()).unwrap();
-        file.sync_all().unwrap();
-
-        let config = match TestConfig::get_config_no_verify("ovc_test_get_config_no_verify.ini") {
-            Ok(c) => {
-                std::fs::remove_file("ovc_test_get_config_no_verify.ini").unwrap();
-                c
-            }
-            Err(e) => {
-                std::fs::remove_file("ovc_test_get_config_no_verify.ini").unwrap();
-                panic!(e.to_string());
-            }
+    fn ovc_test_origin() {
+        let dir = "ovc_test_origin";
+        std::fs::create_dir_all(dir).unwrap();
+        let main = format!("{}/main.ini", dir);
+        let common = format!("{}/common.ini", dir);
+        std::fs::write(&main, "@include = common.ini\n[SECTION2]\n\na_i32: 12\n").unwrap();
+        std::fs::write(&common, "[SECTION1]\na_vector: [4]\n").unwrap();
+        std::env::set_var("OVCTESTORIGIN_SECTION2_A_BOOL", "false");
+
+        let mut config = TestConfig::layered()
+            .file(&main)
+            .env("OVCTESTORIGIN")
+            .cli(vec!["SECTION1.a_string=cli"])
+            .build()
+            .unwrap();
+        std::env::remove_var("OVCTESTORIGIN_SECTION2_A_BOOL");
+        std::fs::remove_dir_all(dir).unwrap();
+
+        let file = |path: &str, line| Origin::File {
+            path: path.into(),
+            line: Some(line),
         };
+        assert_eq!(config.origin("SECTION2", "a_i32"), Some(file(&main, 4)));
+        assert_eq!(
+            config.origin("SECTION1", "a_vector"),
+            Some(file(&common, 2))
+        );
+        assert_eq!(
+            config.origin("SECTION2", "a_bool"),
+            Some(Origin::Env("OVCTESTORIGIN_SECTION2_A_BOOL".into()))
+        );
+        assert_eq!(config.origin("SECTION1", "a_string"), Some(Origin::Cli));
+        assert_eq!(config.origin("SECTION1", "a_i32"), None);
+        assert_eq!(config.origin("SECTION3", "a_i32"), None);
 
-        assert_eq!(config.SECTION1.a_string, "i_am_a_string");
-        assert_eq!(config.SECTION1.a_vector, [1, 2, 3]);
-        assert_eq!(config.SECTION2.a_i32, 128);
-        assert_eq!(config.SECTION2.a_bool, true);
+        config.set_raw("SECTION2", "a_i32", "13").unwrap();
+        assert_eq!(config.origin("SECTION2", "a_i32"), Some(Origin::Set));
+        assert_eq!(
+            config.dump_origins(),
+            format!(
+                "SECTION1.a_string = cli (command line)\n\
+                 SECTION1.a_vector = [4] ({}:2)\n\
+                 SECTION2.a_i32 = 13 (set)\n\
+                 SECTION2.a_bool = false (env OVCTESTORIGIN_SECTION2_A_BOOL)\n",
+                common
+            )
+        );
+
+        let config = TestConfig::from_str("[SECTION2]\na_i32: 12\n", Format::Ini).unwrap();
+        assert_eq!(
+            config.origin("SECTION2", "a_i32").unwrap().to_string(),
+            "<string>:2"
+        );
+        assert_eq!(config.origin("SECTION2", "a_bool"), Some(Origin::Default));
+        assert_eq!(
+            config,
+            TestConfig::from_str("[SECTION2]\na_i32: 12\n", Format::Ini).unwrap()
+        );
     }
 
     #[test]
-    fn ovc_test_refresh() {
-        let config = r#"
-        [SECTION1]
-        a_string: i_am_a_string
-        a_vector: [1, 2, 3]
-        [SECTION2]
-        a_i32: 12
-        a_bool: true
-        "#;
+    fn ovc_test_strict() {
+        assert_eq!(
+            schema::suggest("a_i23", &["a_i32", "a_bool"]),
+            Some("a_i32")
+        );
+        assert_eq!(
+            schema::suggest("section2", &["SECTION1", "SECTION2"]),
+            Some("SECTION2")
+        );
+        assert_eq!(schema::suggest("port", &["a_i32", "a_bool"]), None);
 
-        let mut file = File::create("ovc_test_refresh.ini").unwrap();
-        file.write_all(config.as_bytes()).unwrap();
-        file.sync_all().unwrap();
+        let text = "port = 1\n[include]\nx = a.ini\n";
+        let doc = Document::load_from_str(text, Format::Ini).unwrap();
+        let errors = schema::unknown(&doc, Some(text), TestConfig::SECTION_KEYS);
+        let errors = errors.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            errors,
+            ["UnknownKey: port is outside any section at line 1"]
+        );
+        let errors = schema::unknown(&doc, Some(text), &[("include", &["path"])]);
+        let errors = errors.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            errors,
+            [
+                "UnknownKey: port is outside any section at line 1",
+                "UnknownKey: [include]::x at line 3",
+            ]
+        );
 
-        let mut config = TestConfig::get_config("ovc_test_refresh.ini").unwrap();
+        let dir = "ovc_test_strict";
+        std::fs::create_dir_all(dir).unwrap();
+        let main = format!("{}/main.ini", dir);
+        let common = format!("{}/common.toml", dir);
+        std::fs::write(
+            &main,
+            "@include = common.toml\n[SECTION2]\na_i23: 12\n[SECTON1]\nx: 1\n",
+        )
+        .unwrap();
+        std::fs::write(&common, "[SECTION1]\na_strin = \"x\"\n").unwrap();
+
+        let (config, warnings) = TestConfig::get_config_lenient(&main).unwrap();
+        assert_eq!(config.SECTION2.a_i32, 15);
+        let warnings = warnings.iter().map(|w| w.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            warnings,
+            [
+                format!(
+                    "IncludeError: {} -> {}: UnknownKey: [SECTION1]::a_strin at line 2. Did you mean a_string?",
+                    main, common
+                ),
+                "UnknownKey: [SECTION2]::a_i23 at line 3. Did you mean a_i32?".to_string(),
+                "UnknownSection: [SECTON1] at line 4. Did you mean SECTION1?".to_string(),
+            ]
+        );
+        match TestConfig::get_config_strict(&main) {
+            Err(OVConfigError::Multiple(errors)) => assert_eq!(errors.len(), 3),
+            r => panic!("{:?}", r),
+        }
 
-        assert_eq!(config.SECTION1.a_string, "i_am_a_string");
-        assert_eq!(config.SECTION1.a_vector, [1, 2, 3]);
+        std::fs::write(&main, "[SECTION2]\na_i32: 12\n").unwrap();
+        let mut config = TestConfig::get_config_strict(&main).unwrap();
+        assert!(TestConfig::layered().file(&main).build_strict().is_ok());
+        std::fs::write(&main, "[SECTION2]\na_i32: 13\na_boll: false\n").unwrap();
+        let refreshed = config.refresh();
+        let layered = TestConfig::layered().file(&main).build_strict();
+        let (_, lenient) = TestConfig::layered().file(&main).build_lenient().unwrap();
+        std::fs::remove_dir_all(dir).unwrap();
+
+        match refreshed {
+            Err(OVConfigError::UnknownKey {
+                key,
+                suggestion,
+                line,
+                ..
+            }) => {
+                assert_eq!(key, "a_boll");
+                assert_eq!(suggestion, Some("a_bool"));
+                assert_eq!(line, Some(3));
+            }
+            r => panic!("{:?}", r),
+        }
         assert_eq!(config.SECTION2.a_i32, 12);
-        assert_eq!(config.SECTION2.a_bool, true);
-
-        let cfg = r#"
-        [SECTION1]
-        a_string: i_am_a_string
-        a_vector: [1, 2, 3]
-        [SECTION2]
-        a_i32: 13
-        a_bool: true
-        "#;
-
-        let mut file = File::create("ovc_test_refresh.ini").unwrap();
-        file.write_all(cfg.as_bytes()).unwrap();
-        file.sync_all().unwrap();
+        assert!(layered.is_err());
+        assert_eq!(lenient.len(), 1);
 
-        match config.refresh() {
-            Ok(_) => std::fs::remove_file("ovc_test_refresh.ini").unwrap(),
-            Err(e) => {
-                std::fs::remove_file("ovc_test_refresh.ini").unwrap();
-                panic!(e.to_string());
-            }
-        };
-        assert_eq!(config.SECTION2.a_i32, 13);
+        match TestConfig::default().set_raw("SECTION2", "a_i33", "1") {
+            Err(e) => assert_eq!(
+                e.to_string(),
+                "UnknownKey: [SECTION2]::a_i33. Did you mean a_i32?"
+            ),
+            r => panic!("{:?}", r),
+        }
     }
 
-    #[test]
-    fn ovc_test_refresh_verify_error() {
-        let config = r#"
-        [SECTION1]
-        a_string: i_am_a_string
-        a_vector: [1, 2, 3]
-        [SECTION2]
-        a_i32: 12
-        a_bool: true
-        "#;
-
-        let mut file = File::create("ovc_test_refresh_verify_error.ini").unwrap();
-        file.write_all(config.as_bytes()).unwrap();
-        file.sync_all().unwrap();
-
-        let mut config = TestConfig::get_config("ovc_test_refresh_verify_error.ini").unwrap();
+    mod required {
+        use super::*;
 
-        assert_eq!(config.SECTION1.a_string, "i_am_a_string");
-        assert_eq!(config.SECTION1.a_vector, [1, 2, 3]);
-        assert_eq!(config.SECTION2.a_i32, 12);
-        assert_eq!(config.SECTION2.a_bool, true);
+        make_config!(RequiredConfig, DB {
+            url: String => |x: &String| !x.is_empty(),
+            port: u16: 5432 => |_| true
+        });
 
-        let cfg = r#"
-        [SECTION1]
-        a_string: i_am_a_string
-        a_vector: [1, 2, 3]
-        [SECTION2]
-        a_i32: 139
-        a_bool: true
-        "#;
+        #[test]
+        fn ovc_test_required() {
+            let config = RequiredConfig::from_str("[DB]\nurl: db.local\n", Format::Ini).unwrap();
+            assert_eq!(config.DB.url, "db.local");
+            assert_eq!(config.DB.port, 5432);
 
-        let mut file = File::create("ovc_test_refresh_verify_error.ini").unwrap();
-        file.write_all(cfg.as_bytes()).unwrap();
-        file.sync_all().unwrap();
+            match RequiredConfig::from_str("[DB]\nport = 1\n", Format::Toml) {
+                Err(OVConfigError::MissingKey { section, key }) => {
+                    assert_eq!(section, "DB");
+                    assert_eq!(key, "url");
+                }
+                r => panic!("{:?}", r),
+            }
 
-        match config.refresh() {
-            Ok(_) => {
-                std::fs::remove_file("ovc_test_refresh_verify_error.ini").unwrap();
-                panic!("Should not be OK.");
+            std::fs::write("ovc_test_required.ini", "[DB]\nport: 1\n").unwrap();
+            let missing = RequiredConfig::get_config("ovc_test_required.ini");
+            let layered = RequiredConfig::layered()
+                .file("ovc_test_required.ini")
+                .cli(vec!["DB.url=db.local"])
+                .build();
+            std::fs::write("ovc_test_required.ini", "[DB]\nurl: db.local\n").unwrap();
+            let mut config = RequiredConfig::get_config("ovc_test_required.ini").unwrap();
+            std::fs::write("ovc_test_required.ini", "[DB]\nport: 2\n").unwrap();
+            let refreshed = config.refresh();
+            std::fs::remove_file("ovc_test_required.ini").unwrap();
+
+            match missing {
+                Err(e) => assert_eq!(e.to_string(), "MissingKey: [DB]::url"),
+                r => panic!("{:?}", r),
             }
-            Err(e) => {
-                std::fs::remove_file("ovc_test_refresh_verify_error.ini").unwrap();
-                assert_eq!(
-                    "OVConfigError: Bad [SECTION2]::a_i32. Found: 139",
-                    e.to_string()
-                );
+            let layered = layered.unwrap();
+            assert_eq!(layered.DB.url, "db.local");
+            assert_eq!(layered.DB.port, 1);
+            match refreshed {
+                Err(OVConfigError::MissingKey { .. }) => {}
+                r => panic!("{:?}", r),
             }
-        };
+            assert_eq!(config.DB.url, "db.local");
+
+            let schema = RequiredConfig::schema();
+            assert_eq!(schema[0].default, None);
+            assert_eq!(schema[1].default.as_deref(), Some("5432"));
+        }
+    }
+
+    mod optional {
+        use super::*;
+
+        make_config!(OptionalConfig, SECTION3 {
+            port: Option<u16>: None => |x: &Option<u16>| x.is_none_or(|p| p > 0),
+            name: Option<String>: None => |_| true,
+            host: Option<String> => |_| true
+        });
+
+        #[test]
+        fn ovc_test_optional() {
+            // `host` has no default value, but being optional it does not prevent `Default`.
+            assert_eq!(
+                OptionalConfig::default(),
+                OptionalConfig::from_str("", Format::Ini).unwrap()
+            );
+
+            let config =
+                OptionalConfig::from_str("[SECTION3]\nport: 80\nhost: a.local\n", Format::Ini)
+                    .unwrap();
+            assert_eq!(config.SECTION3.port, Some(80));
+            assert_eq!(config.SECTION3.name, None);
+            assert_eq!(config.SECTION3.host.as_deref(), Some("a.local"));
+
+            let config = OptionalConfig::from_str("[SECTION3]\nname =\n", Format::Ini).unwrap();
+            assert_eq!(config.SECTION3.port, None);
+            assert_eq!(config.SECTION3.name, None);
+            assert_eq!(config.SECTION3.host, None);
+            assert_eq!(config.to_string().unwrap(), "");
+
+            let config = OptionalConfig::from_str("[SECTION3]\nport = 81\n", Format::Toml).unwrap();
+            assert_eq!(config.SECTION3.port, Some(81));
+            assert_eq!(
+                config.SECTION3.to_string(Format::Toml).unwrap(),
+                "[SECTION3]\nport = 81\n"
+            );
+
+            std::fs::write(
+                "ovc_test_optional.ini",
+                "# net\n[SECTION3]\nport: 80\nname: ovd\nhost: a.local\n",
+            )
+            .unwrap();
+            let mut config = OptionalConfig::get_config("ovc_test_optional.ini").unwrap();
+            assert_eq!(config.SECTION3.name.as_deref(), Some("ovd"));
+            config.SECTION3.port = None;
+            config.SECTION3.name = None;
+            config.flush_preserving().unwrap();
+            let preserved = std::fs::read_to_string("ovc_test_optional.ini").unwrap();
+            config.flush().unwrap();
+            let flushed = std::fs::read_to_string("ovc_test_optional.ini").unwrap();
+            let reloaded = OptionalConfig::get_config("ovc_test_optional.ini").unwrap();
+            std::fs::remove_file("ovc_test_optional.ini").unwrap();
+
+            assert_eq!(preserved, "# net\n[SECTION3]\nhost: a.local\n");
+            assert_eq!(flushed, "[SECTION3]\nhost=\"a.local\"\n");
+            // Left out of the file and read back as `None`.
+            assert_eq!(reloaded, config);
+            assert!(OptionalConfig::from_str("[SECTION3]\nport: 0\n", Format::Ini).is_err());
+        }
     }
 
     #[test]
-    fn ovc_test_refresh_no_verify() {
-        let config = r#"
-        [SECTION1]
-        a_string: i_am_a_string
-        a_vector: [1, 2, 3]
-        [SECTION2]
-        a_i32: 12
-        a_bool: true
-        "#;
+    fn ovc_test_sample_config() {
+        assert_eq!(
+            TestConfig::section_descriptions(),
+            [
+                ("SECTION1", "Strings and lists.".to_string()),
+                ("SECTION2", String::new())
+            ]
+        );
+        assert_eq!(
+            TestConfig::schema()[1].description,
+            "At most three items.\n\nWritten as a JSON array in INI files."
+        );
 
-        let mut file = File::create("ovc_test_refresh_no_verify.ini").unwrap();
-        file.write_all(config.as_bytes()).unwrap();
-        file.sync_all().unwrap();
+        let sample = TestConfig::sample_config(Format::Ini).unwrap();
+        assert_eq!(
+            sample,
+            "# Strings and lists.\n\
+             [SECTION1]\n\
+             # Must not be empty.\n\
+             # Type: String. Default: key1.\n\
+             a_string=\"key1\"\n\
+             # At most three items.\n\
+             #\n\
+             # Written as a JSON array in INI files.\n\
+             # Type: Vec<i32>. Default: [1,2,3].\n\
+             a_vector=[1,2,3]\n\
+             \n\
+             [SECTION2]\n\
+             # Type: i32. Default: 15.\n\
+             a_i32=15\n\
+             # Type: bool. Default: true.\n\
+             a_bool=true\n"
+        );
+        let config = TestConfig::from_str(&sample, Format::Ini).unwrap();
+        assert_eq!(config.SECTION1, TestConfig::default().SECTION1);
+        assert_eq!(config.SECTION2, TestConfig::default().SECTION2);
 
-        let mut config = TestConfig::get_config("ovc_test_refresh_no_verify.ini").unwrap();
+        TestConfig::write_sample_config("ovc_test_sample.toml").unwrap();
+        let config = TestConfig::get_config("ovc_test_sample.toml").unwrap();
+        std::fs::remove_file("ovc_test_sample.toml").unwrap();
+        assert_eq!(config.SECTION1, TestConfig::default().SECTION1);
 
-        assert_eq!(config.SECTION1.a_string, "i_am_a_string");
-        assert_eq!(config.SECTION1.a_vector, [1, 2, 3]);
-        assert_eq!(config.SECTION2.a_i32, 12);
-        assert_eq!(config.SECTION2.a_bool, true);
+        let sample = required::RequiredConfig::sample_config(Format::Toml).unwrap();
+        assert_eq!(
+            sample,
+            "[DB]\n# Type: String. Required.\n# url =\n# Type: u16. Default: 5432.\nport = 5432\n"
+        );
+        let sample = optional::OptionalConfig::sample_config(Format::Ini).unwrap();
+        assert!(sample.contains("# Type: Option<u16>. Optional.\n# port=\n"));
+    }
 
-        let cfg = r#"
-        [SECTION1]
-        a_string: i_am_a_string
-        a_vector: [1, 2, 3]
-        [SECTION2]
-        a_i32: 130
-        a_bool: true
-        "#;
+    #[test]
+    fn ovc_test_json_schema() {
+        let schema = TestConfig::json_schema();
+        assert_eq!(schema["title"], "TestConfig");
+        assert!(schema.get("required").is_none());
+        let section1 = &schema["properties"]["SECTION1"];
+        assert_eq!(section1["type"], "object");
+        assert_eq!(section1["description"], "Strings and lists.");
+        assert_eq!(
+            section1["properties"]["a_vector"],
+            serde_json::json!({
+                "type": "array",
+                "items": {"type": "integer", "minimum": i32::MIN, "maximum": i32::MAX},
+                "default": [1, 2, 3],
+                "description": "At most three items.\n\nWritten as a JSON array in INI files.",
+            })
+        );
+        assert_eq!(
+            schema["properties"]["SECTION2"]["properties"]["a_bool"]["default"],
+            true
+        );
 
-        let mut file = File::create("ovc_test_refresh_no_verify.ini").unwrap();
-        file.write_all(cfg.as_bytes()).unwrap();
-        file.sync_all().unwrap();
+        let schema = required::RequiredConfig::json_schema();
+        assert_eq!(schema["required"], serde_json::json!(["DB"]));
+        assert_eq!(
+            schema["properties"]["DB"]["required"],
+            serde_json::json!(["url"])
+        );
+        assert_eq!(
+            schema["properties"]["DB"]["properties"]["port"]["maximum"],
+            65535
+        );
 
-        match config.refresh_no_verify() {
-            Ok(_) => std::fs::remove_file("ovc_test_refresh_no_verify.ini").unwrap(),
-            Err(e) => {
-                std::fs::remove_file("ovc_test_refresh_no_verify.ini").unwrap();
-                panic!(e.to_string());
+        let schema = optional::OptionalConfig::json_schema();
+        let port = &schema["properties"]["SECTION3"]["properties"]["port"];
+        assert_eq!(port["type"], serde_json::json!(["integer", "null"]));
+        assert!(port.get("default").is_none());
+        assert!(schema["properties"]["SECTION3"].get("required").is_none());
+    }
+
+    #[test]
+    fn ovc_test_reflection() {
+        assert_eq!(TestConfig::sections(), ["SECTION1", "SECTION2"]);
+        assert_eq!(TestConfig::keys("SECTION2"), Some(&["a_i32", "a_bool"][..]));
+        assert_eq!(TestConfig::keys("SECTION3"), None);
+        assert_eq!(
+            TestConfig::type_name("SECTION1", "a_vector"),
+            Some("Vec<i32>")
+        );
+        assert_eq!(
+            TestConfig::default_value("SECTION1", "a_string"),
+            Some("key1".into())
+        );
+        assert_eq!(required::RequiredConfig::default_value("DB", "url"), None);
+
+        let mut config = TestConfig::default();
+        for section in TestConfig::sections() {
+            for key in TestConfig::keys(section).unwrap() {
+                let value = config.get(section, key).unwrap();
+                assert_eq!(TestConfig::default_value(section, key), Some(value.clone()));
+                config.set(section, key, &value).unwrap();
             }
-        };
-        assert_eq!(config.SECTION2.a_i32, 130);
+        }
+        assert_eq!(config, TestConfig::default());
+
+        config.set("SECTION1", "a_string", "hello world").unwrap();
+        config.set("SECTION2", "a_i32", "18").unwrap();
+        assert_eq!(config.SECTION1.a_string, "hello world");
+        assert_eq!(config.get("SECTION2", "a_i32").unwrap(), "18");
+        assert_eq!(config.origin("SECTION2", "a_i32"), Some(Origin::Set));
+
+        // Rejected by the verification closure, the value is left unchanged.
+        match config.set("SECTION2", "a_i32", "25") {
+            Err(OVConfigError::BadValue { key, .. }) => assert_eq!(key, "a_i32"),
+            e => panic!("unexpected {:?}", e),
+        }
+        assert!(config.set("SECTION1", "a_vector", "[1, 2, 3, 4]").is_err());
+        assert!(config.set("SECTION2", "a_bool", "maybe").is_err());
+        assert_eq!(config.SECTION2.a_i32, 18);
+        assert_eq!(config.SECTION1.a_vector, vec![1, 2, 3]);
+
+        match config.get("SECTION2", "a_i23") {
+            Err(OVConfigError::UnknownKey { suggestion, .. }) => {
+                assert_eq!(suggestion, Some("a_i32"))
+            }
+            e => panic!("unexpected {:?}", e),
+        }
+        assert!(config.set("SECTION9", "a_i32", "1").is_err());
+
+        // Strings are taken as is, backslashes and quotes included.
+        config.set("SECTION1", "a_string", "C:\\dir").unwrap();
+        assert_eq!(config.SECTION1.a_string, "C:\\dir");
+        config.SECTION1.a_string = "say \"hi\\\"".into();
+        let text = config.get("SECTION1", "a_string").unwrap();
+        config.SECTION1.a_string.clear();
+        config.set("SECTION1", "a_string", &text).unwrap();
+        assert_eq!(config.SECTION1.a_string, "say \"hi\\\"");
+
+        let mut config = optional::OptionalConfig::from_str("", Format::Ini).unwrap();
+        assert_eq!(config.get("SECTION3", "port").unwrap(), "");
+        config.set("SECTION3", "port", "8080").unwrap();
+        assert_eq!(config.SECTION3.port, Some(8080));
+        config.set("SECTION3", "port", "").unwrap();
+        assert_eq!(config.SECTION3.port, None);
+        // An empty string has the same text as `None`, and reads back as `None`.
+        config.SECTION3.name = Some(String::new());
+        assert_eq!(config.get("SECTION3", "name").unwrap(), "");
+        config.set("SECTION3", "name", "").unwrap();
+        assert_eq!(config.SECTION3.name, None);
     }
 
     #[test]
-    fn ovc_test_flush() {
-        let mut d = TestConfig {
-            ..Default::default()
-        };
+    fn ovc_test_diff() {
+        let old = TestConfig::default();
+        let mut new = TestConfig::default();
+        assert!(old.diff(&new).unwrap().is_empty());
+
+        new.SECTION1.a_string = "hello".into();
+        new.SECTION2.a_i32 = 18;
+        let diff = old.diff(&new).unwrap();
+        assert_eq!(
+            diff.0,
+            vec![
+                Change {
+                    section: "SECTION1",
+                    key: "a_string",
+                    old: "\"key1\"".into(),
+                    new: "\"hello\"".into(),
+                },
+                Change {
+                    section: "SECTION2",
+                    key: "a_i32",
+                    old: "15".into(),
+                    new: "18".into(),
+                },
+            ]
+        );
+        assert_eq!(diff.sections(), ["SECTION1", "SECTION2"]);
+        assert_eq!(old.SECTION2.diff(&new.SECTION2).unwrap(), diff.0[1..]);
+        assert_eq!(
+            diff.to_string(),
+            "[SECTION1]::a_string: \"key1\" -> \"hello\"\n[SECTION2]::a_i32: 15 -> 18\n"
+        );
+        assert_eq!(
+            diff.to_json()[1],
+            serde_json::json!({"section": "SECTION2", "key": "a_i32", "old": 15, "new": 18})
+        );
 
-        d.c_p_a_t_h = "ovc_test_flush.ini".into();
-        d.flush().unwrap();
-        assert!(std::path::Path::new("ovc_test_flush.ini").exists());
-        let config = TestConfig::get_config("ovc_test_flush.ini").unwrap();
-        assert_eq!(d, config);
-        std::fs::remove_file("ovc_test_flush.ini").unwrap();
+        // JSON whatever the format, with `None` as `null`.
+        let old = optional::OptionalConfig::from_str("", Format::Toml).unwrap();
+        let new =
+            optional::OptionalConfig::from_str("[SECTION3]\nhost = \"a\"\n", Format::Toml).unwrap();
+        assert_eq!(
+            old.diff(&new).unwrap().to_string(),
+            "[SECTION3]::host: null -> \"a\"\n"
+        );
     }
 
-    #[test]
-    fn ovc_test_flush_failed() {
-        let mut d = TestConfig {
-            ..Default::default()
-        };
-        d.SECTION2.a_i32 = 50;
-        d.c_p_a_t_h = "ovc_test_flush_failed.ini".into();
-        match d.flush() {
-            Ok(_) => panic!("Should not be OK"),
-            Err(e) => assert_eq!(
-                "OVConfigError: Bad [SECTION2]::a_i32. Found: 50",
-                e.to_string()
-            ),
-        };
-        assert!(!std::path::Path::new("ovc_test_flush_failed.ini").exists());
+    mod reload {
+        use super::*;
+
+        make_config!(ReloadConfig, SECTION4 {
+            /// Listen port.
+            #[reload(restart)]
+            port: u16: 8080 => |_| true,
+            #[reload(hot)]
+            log_level: String: "info".into() => |x: &String| !x.is_empty(),
+            timeout: u32: 30 => |_| true
+        });
+
+        #[test]
+        fn ovc_test_reload() {
+            assert!(ReloadConfig::is_restart("SECTION4", "port"));
+            assert!(!ReloadConfig::is_restart("SECTION4", "log_level"));
+            assert!(!ReloadConfig::is_restart("SECTION4", "timeout"));
+            assert!(ReloadConfig::schema()[0].restart);
+
+            let path = "ovc_test_reload.ini";
+            std::fs::write(path, "[SECTION4]\nport: 80\n").unwrap();
+            let mut config = ReloadConfig::get_config(path).unwrap();
+            std::fs::write(path, "[SECTION4]\nport: 81\nlog_level: debug\n").unwrap();
+            let hot = config.refresh_hot();
+            let mut restarted = ReloadConfig::get_config(path).unwrap();
+            std::fs::write(path, "[SECTION4]\nport: 82\n").unwrap();
+            let full = restarted.refresh();
+            std::fs::remove_file(path).unwrap();
+
+            let (refresh, warnings) = hot.unwrap();
+            assert_eq!(config.SECTION4.port, 80);
+            assert_eq!(config.SECTION4.log_level, "debug");
+            assert_eq!(
+                config.origin("SECTION4", "port"),
+                Some(Origin::File {
+                    path: path.into(),
+                    line: Some(2)
+                })
+            );
+            assert_eq!(refresh.changed, ["SECTION4"]);
+            assert!(refresh.needs_restart());
+            assert_eq!(refresh.restart[0].new, "81");
+            assert_eq!(warnings.len(), 1);
+            assert_eq!(
+                warnings[0].to_string(),
+                "RestartRequired: [SECTION4]::port changed from 80 to 81. Keeping 80 until restart."
+            );
+
+            let refresh = full.unwrap();
+            assert_eq!(restarted.SECTION4.port, 82);
+            assert_eq!(restarted.SECTION4.log_level, "info");
+            assert_eq!(refresh.restart.len(), 1);
+            assert!(refresh.needs_restart());
+        }
     }
 
+    #[cfg(feature = "serde")]
     #[test]
-    fn ovc_test_flush_no_verfiy() {
-        let mut d = TestConfig {
-            ..Default::default()
-        };
-        d.SECTION2.a_i32 = 50;
-        d.c_p_a_t_h = "ovc_test_flush_no_verfiy.ini".into();
-        d.flush_no_verify().unwrap();
-        assert!(std::path::Path::new("ovc_test_flush_no_verfiy.ini").exists());
-        let config = TestConfig::get_config_no_verify("ovc_test_flush_no_verfiy.ini").unwrap();
-        assert_eq!(d, config);
-        std::fs::remove_file("ovc_test_flush_no_verfiy.ini").unwrap();
+    fn ovc_test_serde() {
+        let mut config = TestConfig::default();
+        config.SECTION2.a_i32 = 12;
+
+        let value = serde_json::to_value(&config).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "SECTION1": {"a_string": "key1", "a_vector": [1, 2, 3]},
+                "SECTION2": {"a_i32": 12, "a_bool": true},
+            })
+        );
+        assert_eq!(serde_json::from_value::<TestConfig>(value).unwrap(), config);
+
+        // Missing keys and sections take their default value.
+        let partial: TestConfig =
+            serde_json::from_str(r#"{"SECTION2": {"a_bool": false}, "other": 1}"#).unwrap();
+        assert_eq!(partial.SECTION1, TestConfig::default().SECTION1);
+        assert_eq!(partial.SECTION2.a_i32, 15);
+        assert!(!partial.SECTION2.a_bool);
+        assert_eq!(partial.c_p_a_t_h, "");
+
+        let toml = toml::to_string(&config).unwrap();
+        assert_eq!(toml::from_str::<TestConfig>(&toml).unwrap(), config);
+
+        let err = serde_json::from_str::<required::RequiredConfig>(r#"{"DB": {"port": 1}}"#);
+        assert!(err.unwrap_err().to_string().contains("missing field `url`"));
+        let err = serde_json::from_str::<required::RequiredConfig>("{}");
+        assert!(err.unwrap_err().to_string().contains("missing field `url`"));
+
+        let optional: optional::OptionalConfig =
+            serde_json::from_str(r#"{"SECTION3": {"port": 80, "name": "ovd"}}"#).unwrap();
+        assert_eq!(optional.SECTION3.port, Some(80));
+        assert_eq!(optional.SECTION3.name.as_deref(), Some("ovd"));
+        assert_eq!(optional.SECTION3.host, None);
     }
 }
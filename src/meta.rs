@@ -0,0 +1,25 @@
+use super::*;
+
+/// How a generated configuration was loaded, kept in its hidden `m_e_t_a` field.
+///
+/// Used to load it again on `refresh` and to write it back on `flush`. Not part of the
+/// configuration's equality, which only compares the path and the values.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Meta {
+    /// Format of `c_p_a_t_h`, picked from the file extension if `None`.
+    pub format: Option<Format>,
+    /// Prefix of the environment variables applied after loading, see `with_env`.
+    pub env_prefix: Option<String>,
+    /// Overrides applied after loading, see `with_overrides`.
+    pub overrides: Vec<String>,
+    /// Layers the configuration was loaded from, see `layered`.
+    pub sources: Vec<Source>,
+    /// Files pulled in by include directives, see `include`.
+    pub includes: Vec<String>,
+    /// Whether unknown sections and keys are errors, see `get_config_strict`.
+    pub strict: bool,
+    /// Whether flush overwrites files in place instead of replacing them, see `with_in_place_writes`.
+    pub in_place: bool,
+    /// Where the value of every key came from, see `origin`.
+    pub origins: Origins,
+}
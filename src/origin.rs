@@ -0,0 +1,56 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Where the effective value of a key came from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Origin {
+    /// The default value of the schema.
+    Default,
+    /// A configuration file. `path` is empty for a string or reader, `line` starts from 1.
+    File { path: String, line: Option<u32> },
+    /// An environment variable, see `with_env`.
+    Env(String),
+    /// A command-line override, see `with_overrides`.
+    Cli,
+    /// Set by the program through `set_raw`.
+    Set,
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Origin::Default => write!(f, "default"),
+            Origin::File { path, line } => {
+                write!(f, "{}", if path.is_empty() { "<string>" } else { path })?;
+                if let Some(line) = line {
+                    write!(f, ":{}", line)?;
+                }
+                Ok(())
+            }
+            Origin::Env(name) => write!(f, "env {}", name),
+            Origin::Cli => write!(f, "command line"),
+            Origin::Set => write!(f, "set"),
+        }
+    }
+}
+
+/// Origin of every key that does not have its default value.
+///
+/// Kept in the hidden `m_e_t_a` field of a generated configuration, indexed by section then key.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Origins(BTreeMap<&'static str, BTreeMap<&'static str, Origin>>);
+
+impl Origins {
+    /// Origin of `key` in `section`, `Origin::Default` if it was never set.
+    pub fn get(&self, section: &str, key: &str) -> Origin {
+        self.0
+            .get(section)
+            .and_then(|keys| keys.get(key))
+            .map_or(Origin::Default, Origin::clone)
+    }
+
+    /// Record the origin of `key` in `section`.
+    pub fn set(&mut self, section: &'static str, key: &'static str, origin: Origin) {
+        self.0.entry(section).or_default().insert(key, origin);
+    }
+}
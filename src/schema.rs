@@ -0,0 +1,306 @@
+//! Description of generated schemas, and checks of documents against them.
+use super::*;
+
+/// Description of one key of a schema generated by `make_config!`.
+///
+/// Returned by the generated `schema()` function.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyInfo {
+    pub section: &'static str,
+    pub key: &'static str,
+    /// Name of the Rust type, as written in the schema.
+    pub type_name: &'static str,
+    /// Default value as accepted by `set_raw`, `None` for a required key.
+    pub default: Option<String>,
+    /// Doc comments of the key in the schema.
+    pub description: String,
+    /// Whether the key only takes effect after a restart, i.e. is marked `#[reload(restart)]`.
+    pub restart: bool,
+}
+
+/// Join the lines of a doc comment, as given by `#[doc = ...]` attributes.
+pub fn doc(lines: &[&str]) -> String {
+    lines
+        .iter()
+        .map(|l| l.strip_prefix(' ').unwrap_or(l).trim_end())
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string()
+}
+
+/// Append the header of a section to a sample file, see the generated `sample_config`.
+pub fn sample_section(out: &mut String, section: &str, description: &str) {
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    comment(out, description);
+    out.push_str(&format!("[{}]\n", section));
+}
+
+/// Append a key to a sample file, commented out if `raw` is `None`.
+pub fn sample_key(out: &mut String, info: &KeyInfo, raw: Option<&str>, format: Format) {
+    comment(out, &info.description);
+    let default = match (&info.default, raw) {
+        (None, _) => "Required".to_string(),
+        (Some(_), None) => "Optional".to_string(),
+        (Some(default), Some(_)) => format!("Default: {}", default),
+    };
+    comment(out, &format!("Type: {}. {}.", info.type_name, default));
+    let sep = match format {
+        Format::Ini => "=",
+        Format::Toml => " = ",
+    };
+    match raw {
+        Some(raw) => out.push_str(&format!("{}{}{}\n", info.key, sep, raw)),
+        None => out.push_str(&format!("# {}{}\n", info.key, sep.trim_end())),
+    }
+}
+
+fn comment(out: &mut String, text: &str) {
+    for line in text.lines() {
+        if line.is_empty() {
+            out.push_str("#\n");
+        } else {
+            out.push_str(&format!("# {}\n", line));
+        }
+    }
+}
+
+/// Every section and key of `doc` that `schema` does not define.
+///
+/// `schema` lists each section with its keys. Include directives are not reported, nor is an
+/// `[include]` section unless the schema defines one. Errors are
+/// `OVConfigError::UnknownSection` or `OVConfigError::UnknownKey` with a suggestion if a defined
+/// name is close enough, and the line if `text` is given, ordered by line.
+pub fn unknown(
+    doc: &Document,
+    text: Option<&str>,
+    schema: &[(&'static str, &'static [&'static str])],
+) -> Vec<OVConfigError> {
+    let mut found: Vec<(String, Vec<String>)> = Vec::new();
+    match doc {
+        Document::Ini(ini) => {
+            for (section, props) in ini {
+                let section = section.clone().unwrap_or_default();
+                found.push((section, props.keys().cloned().collect()));
+            }
+        }
+        Document::Toml(toml) => {
+            let mut general = Vec::new();
+            for (name, value) in toml.table() {
+                match value {
+                    toml::Value::Table(t) => {
+                        found.push((name.clone(), t.keys().cloned().collect()))
+                    }
+                    _ => general.push(name.clone()),
+                }
+            }
+            found.push((String::new(), general));
+        }
+    }
+
+    let sections: Vec<&'static str> = schema.iter().map(|(s, _)| *s).collect();
+    let mut res = Vec::new();
+    for (section, keys) in found {
+        if section == "include" && !sections.contains(&"include") {
+            continue;
+        }
+        let known = schema.iter().find(|(s, _)| *s == section);
+        if section.is_empty() {
+            for key in keys.iter().filter(|k| *k != "@include") {
+                res.push(unknown_key(&section, key, &[]));
+            }
+        } else if let Some((_, known)) = known {
+            for key in keys.iter().filter(|k| !known.contains(&k.as_str())) {
+                res.push(unknown_key(&section, key, known));
+            }
+        } else if !keys.is_empty() || doc.format() == Format::Ini {
+            res.push(unknown_section(&section, &sections));
+        }
+    }
+
+    let mut res: Vec<OVConfigError> = match text {
+        Some(text) => res.into_iter().map(|e| e.with_line(text)).collect(),
+        None => res,
+    };
+    res.sort_by_key(|e| match e {
+        OVConfigError::UnknownSection { line, .. } | OVConfigError::UnknownKey { line, .. } => {
+            *line
+        }
+        _ => None,
+    });
+    res
+}
+
+/// Turn the unknown sections and keys found by `unknown` into an error, if any.
+///
+/// A single one is returned as is, several as `OVConfigError::Multiple`.
+pub fn reject(mut unknown: Vec<OVConfigError>) -> Result<(), OVConfigError> {
+    match unknown.len() {
+        0 => Ok(()),
+        1 => Err(unknown.remove(0)),
+        _ => Err(OVConfigError::Multiple(unknown)),
+    }
+}
+
+/// `OVConfigError::UnknownSection`, suggesting the closest of `sections`.
+pub fn unknown_section(section: &str, sections: &[&'static str]) -> OVConfigError {
+    OVConfigError::UnknownSection {
+        section: section.into(),
+        suggestion: suggest(section, sections),
+        line: None,
+    }
+}
+
+/// `OVConfigError::UnknownKey`, suggesting the closest of `keys`.
+pub fn unknown_key(section: &str, key: &str, keys: &[&'static str]) -> OVConfigError {
+    OVConfigError::UnknownKey {
+        section: section.into(),
+        key: key.into(),
+        suggestion: suggest(key, keys),
+        line: None,
+    }
+}
+
+/// The candidate closest to `name` by edit distance, ignoring case, if it is close enough.
+pub fn suggest(name: &str, candidates: &[&'static str]) -> Option<&'static str> {
+    let name = name.to_lowercase();
+    let max = (name.chars().count() / 3).max(2);
+    candidates
+        .iter()
+        .map(|c| (distance(&name, &c.to_lowercase()), *c))
+        .filter(|(d, _)| *d <= max)
+        .min_by_key(|(d, _)| *d)
+        .map(|(_, c)| c)
+}
+
+/// Levenshtein distance between two strings, counted in characters.
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cur = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                prev
+            } else {
+                prev.min(cur).min(row[j]) + 1
+            };
+            prev = cur;
+        }
+    }
+    row[b.len()]
+}
+
+/// JSON Schema of a generated configuration, see the generated `json_schema`.
+///
+/// # Argument:
+/// - title: Name of the configuration struct.
+/// - sections: Every section with its description, as given by `section_descriptions`.
+/// - keys: Every key, as given by `schema`.
+/// - defaults: Default value of each key in `keys`, `None` for a required key.
+pub fn json_schema(
+    title: &str,
+    sections: &[(&'static str, String)],
+    keys: &[KeyInfo],
+    defaults: &[Option<serde_json::Value>],
+) -> serde_json::Value {
+    use serde_json::{json, Map, Value};
+
+    let mut properties = Map::new();
+    let mut required_sections = Vec::new();
+    for (section, description) in sections {
+        let mut props = Map::new();
+        let mut required = Vec::new();
+        for (info, default) in keys
+            .iter()
+            .zip(defaults)
+            .filter(|(i, _)| i.section == *section)
+        {
+            let mut prop = json_type(info.type_name);
+            if !info.description.is_empty() {
+                prop["description"] = json!(info.description);
+            }
+            match default {
+                Some(Value::Null) => (),
+                Some(default) => prop["default"] = default.clone(),
+                None => required.push(info.key),
+            }
+            props.insert(info.key.into(), prop);
+        }
+
+        let mut schema = json!({"type": "object", "properties": props});
+        if !description.is_empty() {
+            schema["description"] = json!(description);
+        }
+        if !required.is_empty() {
+            schema["required"] = json!(required);
+            required_sections.push(*section);
+        }
+        properties.insert(section.to_string(), schema);
+    }
+
+    let mut schema = json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": title,
+        "type": "object",
+        "properties": properties,
+    });
+    if !required_sections.is_empty() {
+        schema["required"] = json!(required_sections);
+    }
+    schema
+}
+
+/// JSON Schema of a Rust type, from its name as written in the schema.
+///
+/// Integer ranges are derived from the integer type. Types that cannot be described from their
+/// name, such as user types, are left unconstrained.
+pub fn json_type(type_name: &str) -> serde_json::Value {
+    use serde_json::json;
+
+    let t: String = type_name.chars().filter(|c| !c.is_whitespace()).collect();
+    let t = t.rsplit("::").next().unwrap_or(&t);
+    let generic = |name: &str| {
+        t.strip_prefix(name)
+            .and_then(|r| r.strip_prefix('<'))
+            .and_then(|r| r.strip_suffix('>'))
+    };
+    if let Some(inner) = generic("Option") {
+        let mut inner = json_type(inner);
+        if let Some(t) = inner.get("type").cloned() {
+            inner["type"] = json!([t, "null"]);
+        }
+        return inner;
+    }
+    if let Some(inner) = generic("Vec").or_else(|| generic("VecDeque")) {
+        return json!({"type": "array", "items": json_type(inner)});
+    }
+    if let Some(inner) = generic("HashSet").or_else(|| generic("BTreeSet")) {
+        return json!({"type": "array", "items": json_type(inner), "uniqueItems": true});
+    }
+    if let Some(inner) = generic("HashMap").or_else(|| generic("BTreeMap")) {
+        let value = inner.split_once(',').map_or("", |(_, v)| v);
+        return json!({"type": "object", "additionalProperties": json_type(value)});
+    }
+
+    let int = |min: i128, max: u128| json!({"type": "integer", "minimum": min, "maximum": max});
+    match t {
+        "bool" => json!({"type": "boolean"}),
+        "String" | "str" | "PathBuf" => json!({"type": "string"}),
+        "char" => json!({"type": "string", "minLength": 1, "maxLength": 1}),
+        "f32" | "f64" => json!({"type": "number"}),
+        "i8" => int(i8::MIN.into(), i8::MAX as u128),
+        "i16" => int(i16::MIN.into(), i16::MAX as u128),
+        "i32" => int(i32::MIN.into(), i32::MAX as u128),
+        "u8" => int(0, u8::MAX.into()),
+        "u16" => int(0, u16::MAX.into()),
+        "u32" => int(0, u32::MAX.into()),
+        "u64" | "u128" | "usize" => json!({"type": "integer", "minimum": 0}),
+        "i64" | "i128" | "isize" => json!({"type": "integer"}),
+        _ => json!({}),
+    }
+}
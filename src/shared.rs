@@ -0,0 +1,85 @@
+use super::*;
+use arc_swap::ArcSwap;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+type Listener<C> = Arc<dyn Fn(&C, &C) + Send + Sync>;
+
+// A panicking callback must not leave the handle unusable, so poisoned locks are taken over.
+pub(crate) fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Thread-safe handle to a configuration that can be reloaded while it is being read.
+///
+/// The configuration is kept behind an atomically swappable `Arc`. Readers take cheap,
+/// consistent snapshots with `load()` and never block. Writers reload or replace the
+/// configuration and publish the new version in one swap, then notify every subscriber.
+pub struct Shared<C> {
+    current: ArcSwap<C>,
+    listeners: Mutex<Vec<Listener<C>>>,
+    // Serializes writers so that no update is lost between load and swap.
+    write: Mutex<()>,
+}
+
+impl<C: OVConfig> Shared<C> {
+    /// Wrap a configuration.
+    pub fn new(config: C) -> Self {
+        Shared {
+            current: ArcSwap::from_pointee(config),
+            listeners: Mutex::new(Vec::new()),
+            write: Mutex::new(()),
+        }
+    }
+
+    /// Snapshot of the current configuration.
+    pub fn load(&self) -> Arc<C> {
+        self.current.load_full()
+    }
+
+    /// Publish a new configuration and notify subscribers.
+    pub fn store(&self, config: C) {
+        let _guard = lock(&self.write);
+        self.publish(Arc::new(config));
+    }
+
+    /// Load and verify the configuration file, then publish it if anything changed.
+    ///
+    /// On any error the current configuration is kept.
+    ///
+    /// # Return:
+    /// Will return whether a new configuration was published.
+    pub fn reload(&self) -> Result<bool, OVConfigError> {
+        let _guard = lock(&self.write);
+        let old = self.current.load_full();
+        let new = old.reload()?;
+        if new == *old {
+            return Ok(false);
+        }
+        self.publish(Arc::new(new));
+        Ok(true)
+    }
+
+    /// Flush the current configuration to its file.
+    pub fn flush(&self) -> Result<(), OVConfigError> {
+        let _guard = lock(&self.write);
+        self.current.load().flush()
+    }
+
+    /// Register a listener called with the old and the new configuration after each swap.
+    ///
+    /// Listeners run in order on the writer's thread and must not write to the same handle.
+    /// A listener that panics is skipped; the others are still called and the handle keeps
+    /// working.
+    pub fn subscribe<F: Fn(&C, &C) + Send + Sync + 'static>(&self, f: F) {
+        lock(&self.listeners).push(Arc::new(f));
+    }
+
+    fn publish(&self, new: Arc<C>) {
+        let old = self.current.swap(new.clone());
+        let listeners = lock(&self.listeners).clone();
+        for f in listeners {
+            let _ = catch_unwind(AssertUnwindSafe(|| f(&old, &new)));
+        }
+    }
+}
@@ -0,0 +1,138 @@
+//! Layered configuration sources.
+//!
+//! Layers are applied in order on top of the built-in defaults. Each layer only overrides the
+//! keys it sets, then the result is verified once.
+use super::*;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+
+/// One layer of a layered configuration.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Source {
+    /// A file that must exist. The format is picked from the file extension.
+    File(String),
+    /// A file that is skipped if it does not exist, e.g. a per-user file.
+    OptionalFile(String),
+    /// Every fragment of a drop-in directory, in lexical order. See `fragments`.
+    DropInDir(String),
+    /// Environment variables under the given prefix, see `with_env`.
+    Env(String),
+    /// Overrides of the form `SECTION.key=value`, see `with_overrides`.
+    Cli(Vec<String>),
+}
+
+/// Builder of a configuration made of several layers.
+///
+/// Created by the generated `layered()`. The loaded configuration keeps its sources, so
+/// `refresh` reloads every layer together. `flush` writes to the last file layer.
+#[derive(Debug, PartialEq)]
+pub struct Layers<C> {
+    sources: Vec<Source>,
+    config: PhantomData<C>,
+}
+
+impl<C: OVConfig> Layers<C> {
+    pub fn new() -> Self {
+        Layers {
+            sources: Vec::new(),
+            config: PhantomData,
+        }
+    }
+
+    /// Add a layer.
+    pub fn source(mut self, source: Source) -> Self {
+        self.sources.push(source);
+        self
+    }
+
+    /// Add a file that must exist.
+    pub fn file<T: AsRef<str> + ?Sized>(self, path: &T) -> Self {
+        self.source(Source::File(path.as_ref().into()))
+    }
+
+    /// Add a file that is skipped if it does not exist.
+    pub fn optional_file<T: AsRef<str> + ?Sized>(self, path: &T) -> Self {
+        self.source(Source::OptionalFile(path.as_ref().into()))
+    }
+
+    /// Add every fragment of a drop-in directory.
+    pub fn drop_in_dir<T: AsRef<str> + ?Sized>(self, path: &T) -> Self {
+        self.source(Source::DropInDir(path.as_ref().into()))
+    }
+
+    /// Add environment variables under `prefix`.
+    pub fn env(self, prefix: &str) -> Self {
+        self.source(Source::Env(prefix.into()))
+    }
+
+    /// Add command-line overrides of the form `SECTION.key=value`.
+    pub fn cli<I, S>(self, overrides: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.source(Source::Cli(overrides.into_iter().map(Into::into).collect()))
+    }
+
+    /// Sources added so far, in order.
+    pub fn sources(&self) -> &[Source] {
+        &self.sources
+    }
+
+    /// Load every layer and verify the result.
+    pub fn build(self) -> Result<C, OVConfigError> {
+        C::from_sources(self.sources)
+    }
+
+    /// Load every layer and verify the result, rejecting unknown sections and keys.
+    pub fn build_strict(self) -> Result<C, OVConfigError> {
+        C::from_sources_strict(self.sources)
+    }
+
+    /// Load every layer and verify the result, returning unknown sections and keys as warnings.
+    pub fn build_lenient(self) -> Result<(C, Vec<OVConfigError>), OVConfigError> {
+        C::from_sources_lenient(self.sources)
+    }
+}
+
+// Not derived, so that `C` does not need to be `Clone`.
+impl<C> Clone for Layers<C> {
+    fn clone(&self) -> Self {
+        Layers {
+            sources: self.sources.clone(),
+            config: PhantomData,
+        }
+    }
+}
+
+impl<C: OVConfig> Default for Layers<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Fragments of a drop-in directory, in lexical order of their file names.
+///
+/// Only `.conf`, `.ini` and `.toml` files are read. Hidden files, such as the temporary files of
+/// `write_atomic`, are skipped. A missing directory has no fragments.
+pub fn fragments<P: AsRef<Path>>(dir: P) -> Result<Vec<PathBuf>, OVConfigError> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut res = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let ext = path.extension().unwrap_or_default();
+        if path.is_file()
+            && !name.starts_with('.')
+            && ["conf", "ini", "toml"].iter().any(|e| ext == *e)
+        {
+            res.push(path);
+        }
+    }
+    res.sort();
+    Ok(res)
+}
@@ -0,0 +1,246 @@
+//! Conversion between raw configuration values and Rust types.
+//!
+//! These are the helpers used by the code generated by `make_config!`.
+use super::*;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Parse a raw INI value into `T`.
+///
+/// Values are parsed as JSON, except for `String` keys, which take the raw text as is.
+/// `Option` keys are parsed as their inner type, and an empty value is `None`.
+///
+/// # Argument:
+/// - section, key: Where the value comes from. Used for error reporting.
+/// - expected: Name of the Rust type, as written in the schema.
+/// - raw: The raw text found in the document.
+pub fn parse_ini<T: DeserializeOwned>(
+    section: &str,
+    key: &str,
+    expected: &'static str,
+    raw: &str,
+) -> Result<T, OVConfigError> {
+    let res = match inner_type(expected) {
+        _ if is_option(expected) && raw.trim().is_empty() => serde_json::from_str("null"),
+        "String" | "str" => serde_json::from_value(serde_json::Value::String(raw.into())),
+        _ => serde_json::from_str(raw),
+    };
+    res.map_err(|e| parse_error(section, key, expected, raw, e.to_string()))
+}
+
+/// Convert a TOML value into `T`.
+///
+/// See `parse_ini` for the arguments.
+pub fn parse_toml<T: DeserializeOwned>(
+    section: &str,
+    key: &str,
+    expected: &'static str,
+    raw: &toml::Value,
+) -> Result<T, OVConfigError> {
+    raw.clone()
+        .try_into()
+        .map_err(|e| parse_error(section, key, expected, &raw.to_string(), e.to_string()))
+}
+
+/// Name of the environment variable that overrides `key` in `[section]`.
+///
+/// This is `<PREFIX>_<SECTION>_<KEY>` in upper case. An empty prefix gives `<SECTION>_<KEY>`.
+pub fn env_name(prefix: &str, section: &str, key: &str) -> String {
+    let name = if prefix.is_empty() {
+        format!("{}_{}", section, key)
+    } else {
+        format!("{}_{}_{}", prefix.trim_end_matches('_'), section, key)
+    };
+    name.to_uppercase()
+}
+
+/// Serialize `value` of a `expected` key to the raw text written for it in the given format.
+///
+/// INI values are written as JSON, the same way `flush` does, except for `String` keys, which are
+/// quoted so that they read back as is. Both are escaped for INI already.
+pub fn to_raw<T: Serialize + ?Sized>(
+    value: &T,
+    expected: &str,
+    format: Format,
+) -> Result<String, OVConfigError> {
+    Ok(match format {
+        Format::Ini => match serde_json::to_value(value)? {
+            serde_json::Value::String(s) if ["String", "str"].contains(&inner_type(expected)) => {
+                quote_ini(&s)
+            }
+            v => escape_ini(&v.to_string()),
+        },
+        Format::Toml => toml::Value::try_from(value)?.to_string(),
+    })
+}
+
+/// Quote and escape `s` for an INI file, so that it reads back as is.
+pub fn quote_ini(s: &str) -> String {
+    format!("\"{}\"", escape_ini(s).replace('"', "\\\""))
+}
+
+/// Serialize `value` to the raw text accepted back by `parse_ini`.
+///
+/// Unlike `to_raw`, `String` keys are not quoted, and `None` is empty, the same as `Some("")` of
+/// an `Option<String>` key.
+pub fn to_text<T: Serialize + ?Sized>(value: &T, expected: &str) -> Result<String, OVConfigError> {
+    Ok(match serde_json::to_value(value)? {
+        serde_json::Value::String(s) if ["String", "str"].contains(&inner_type(expected)) => s,
+        serde_json::Value::Null => String::new(),
+        v => v.to_string(),
+    })
+}
+
+/// `OVConfigError::BadValue` for a value rejected by the verification closure of `key`.
+pub fn bad_value<T: Serialize + ?Sized>(section: &str, key: &str, value: &T) -> OVConfigError {
+    OVConfigError::BadValue {
+        section: section.into(),
+        key: key.into(),
+        value: serde_json::to_string(value).unwrap_or_else(|_| "UNKNOWN".into()),
+    }
+}
+
+/// Whether the type name, as written in the schema, is an `Option`.
+pub const fn is_option(expected: &str) -> bool {
+    let t = expected.as_bytes().trim_ascii();
+    let t = strip_prefix(strip_prefix(t, b"std::option::"), b"core::option::");
+    if !starts_with(t, b"Option") {
+        return false;
+    }
+    let rest = t.split_at(b"Option".len()).1.trim_ascii_start();
+    rest.len() >= 2 && rest[0] == b'<' && rest[rest.len() - 1] == b'>'
+}
+
+/// Whether `default`, as written in the schema, can be the default value of a `expected` key.
+///
+/// An absent `Option` key reads back as `None`, so it is the only default such a key can have.
+/// `make_config!` checks this at compile time.
+pub const fn is_valid_default(expected: &str, default: &str) -> bool {
+    let default = default.as_bytes().trim_ascii();
+    !is_option(expected) || (default.len() == b"None".len() && starts_with(default, b"None"))
+}
+
+const fn starts_with(s: &[u8], prefix: &[u8]) -> bool {
+    if s.len() < prefix.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < prefix.len() {
+        if s[i] != prefix[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+const fn strip_prefix<'a>(s: &'a [u8], prefix: &[u8]) -> &'a [u8] {
+    if starts_with(s, prefix) {
+        s.split_at(prefix.len()).1
+    } else {
+        s
+    }
+}
+
+/// A key of type `T`, see `Optional`.
+#[doc(hidden)]
+pub struct Key<'a, T>(std::marker::PhantomData<&'a T>);
+
+/// Implemented for the keys of `Option` type only.
+///
+/// The generated `Default` requires it of every key without a default value, so that it can
+/// only be called if none of them is required.
+#[doc(hidden)]
+pub trait Optional {}
+
+impl<T> Optional for Key<'_, Option<T>> {}
+
+/// Whether `value` is serialized as nothing, i.e. is `None`.
+pub fn is_null<T: Serialize + ?Sized>(value: &T) -> bool {
+    matches!(serde_json::to_value(value), Ok(serde_json::Value::Null))
+}
+
+/// `T` for `Option<T>`, the type name itself otherwise.
+fn inner_type(expected: &str) -> &str {
+    let t = expected.trim();
+    let t = t
+        .strip_prefix("std::option::")
+        .or_else(|| t.strip_prefix("core::option::"))
+        .unwrap_or(t);
+    match t.strip_prefix("Option").map(str::trim_start) {
+        Some(rest) if rest.starts_with('<') && rest.ends_with('>') => {
+            rest[1..rest.len() - 1].trim()
+        }
+        _ => expected,
+    }
+}
+
+/// Escape a value the same way `Ini` does when writing a file.
+fn escape_ini(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\0' => escaped.push_str("\\0"),
+            '\x07' => escaped.push_str("\\a"),
+            '\x08' => escaped.push_str("\\b"),
+            '\x0b' => escaped.push_str("\\v"),
+            '\x0c' => escaped.push_str("\\f"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            '\x01'..='\x1f' | '\x7f'..='\u{00ff}' => {
+                escaped.push_str(&format!("\\x{:04x}", c as u32))
+            }
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn parse_error(
+    section: &str,
+    key: &str,
+    expected: &'static str,
+    raw: &str,
+    reason: String,
+) -> OVConfigError {
+    OVConfigError::ParseValueError {
+        section: section.into(),
+        key: key.into(),
+        value: raw.into(),
+        expected,
+        line: None,
+        reason,
+    }
+}
+
+/// Find the line that sets `key` in `[section]`.
+///
+/// Works on both INI and TOML text. Line numbers start from 1.
+pub fn find_line(text: &str, section: &str, key: &str) -> Option<u32> {
+    let mut current = "";
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            current = line.trim_start_matches('[').trim_end_matches(']').trim();
+        } else if current == section && line.starts_with(key) {
+            let rest = line[key.len()..].trim_start();
+            if rest.starts_with('=') || rest.starts_with(':') {
+                return Some(i as u32 + 1);
+            }
+        }
+    }
+    None
+}
+
+/// Find the header line of the first `[section]`.
+pub fn find_section(text: &str, section: &str) -> Option<u32> {
+    text.lines()
+        .position(|line| {
+            let line = line.trim();
+            line.starts_with('[')
+                && line.trim_start_matches('[').trim_end_matches(']').trim() == section
+        })
+        .map(|i| i as u32 + 1)
+}
@@ -0,0 +1,169 @@
+use super::*;
+use notify::event::EventKind;
+use notify::{PollWatcher, RecursiveMode, Watcher as _};
+use super::shared::lock;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+type ErrorCallback = Box<dyn FnMut(&OVConfigError) + Send>;
+
+enum Msg {
+    Event(notify::Result<notify::Event>),
+    Stop,
+}
+
+struct Inner<C> {
+    shared: Arc<Shared<C>>,
+    on_error: Mutex<Vec<ErrorCallback>>,
+}
+
+/// Watch the configuration file and refresh the configuration when it changes.
+///
+/// The directory of `c_p_a_t_h` is watched with the native file notification API, falling back
+/// to polling if it is not available, so editors that save by renaming over the file are handled.
+/// Bursts of writes are debounced, then the file is loaded, verified and published through a
+/// `Shared` handle. Registered callbacks get the old and the new configuration after every
+/// successful change.
+///
+/// The watcher stops when it is dropped.
+pub struct Watcher<C> {
+    inner: Arc<Inner<C>>,
+    tx: Sender<Msg>,
+    thread: Option<JoinHandle<()>>,
+    // Kept alive for as long as the watcher runs.
+    _backend: Box<dyn notify::Watcher + Send>,
+}
+
+impl<C: OVConfig + Send + Sync + 'static> Watcher<C> {
+    /// Start watching the file `config` was loaded from.
+    ///
+    /// # Argument:
+    /// - config: The current configuration.
+    /// - debounce: How long the file must stay quiet before it is reloaded.
+    pub fn new(config: C, debounce: Duration) -> Result<Self, OVConfigError> {
+        Self::with_shared(Arc::new(Shared::new(config)), debounce)
+    }
+
+    /// Start watching the file of an existing shared configuration.
+    ///
+    /// Changes are published through `shared`, so its subscribers are notified as well.
+    pub fn with_shared(shared: Arc<Shared<C>>, debounce: Duration) -> Result<Self, OVConfigError> {
+        let target = PathBuf::from(shared.load().path());
+        let dir = match target.parent() {
+            Some(d) if !d.as_os_str().is_empty() => d.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+
+        let (tx, rx) = channel();
+        let backend = start_backend(&dir, &tx, debounce)?;
+        let inner = Arc::new(Inner {
+            shared,
+            on_error: Mutex::new(Vec::new()),
+        });
+
+        let thread_inner = inner.clone();
+        let thread = std::thread::spawn(move || run(thread_inner, rx, target, debounce));
+
+        Ok(Watcher {
+            inner,
+            tx,
+            thread: Some(thread),
+            _backend: backend,
+        })
+    }
+
+    /// Snapshot of the current configuration.
+    pub fn config(&self) -> Arc<C> {
+        self.inner.shared.load()
+    }
+
+    /// The shared handle changes are published through.
+    pub fn shared(&self) -> &Arc<Shared<C>> {
+        &self.inner.shared
+    }
+
+    /// Register a callback called with the old and the new configuration after each change.
+    pub fn on_change<F: Fn(&C, &C) + Send + Sync + 'static>(&self, f: F) {
+        self.inner.shared.subscribe(f);
+    }
+
+    /// Register a callback called when the changed file cannot be loaded or verified.
+    ///
+    /// The current configuration is kept in that case. As with `on_change`, a callback that
+    /// panics is skipped and the watcher keeps running.
+    pub fn on_error<F: FnMut(&OVConfigError) + Send + 'static>(&self, f: F) {
+        lock(&self.inner.on_error).push(Box::new(f));
+    }
+}
+
+impl<C> Drop for Watcher<C> {
+    fn drop(&mut self) {
+        let _ = self.tx.send(Msg::Stop);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn start_backend(
+    dir: &Path,
+    tx: &Sender<Msg>,
+    interval: Duration,
+) -> Result<Box<dyn notify::Watcher + Send>, OVConfigError> {
+    let handler = |tx: Sender<Msg>| move |res| drop(tx.send(Msg::Event(res)));
+
+    if let Ok(mut w) = notify::recommended_watcher(handler(tx.clone())) {
+        if w.watch(dir, RecursiveMode::NonRecursive).is_ok() {
+            return Ok(Box::new(w));
+        }
+    }
+
+    let config = notify::Config::default().with_poll_interval(interval);
+    let mut w = PollWatcher::new(handler(tx.clone()), config).map_err(watch_error)?;
+    w.watch(dir, RecursiveMode::NonRecursive)
+        .map_err(watch_error)?;
+    Ok(Box::new(w))
+}
+
+fn run<C: OVConfig>(inner: Arc<Inner<C>>, rx: Receiver<Msg>, target: PathBuf, debounce: Duration) {
+    let relevant = |msg: &Msg| match msg {
+        Msg::Event(Ok(event)) => {
+            !matches!(event.kind, EventKind::Access(_))
+                && event
+                    .paths
+                    .iter()
+                    .any(|p| p.file_name() == target.file_name())
+        }
+        Msg::Event(Err(_)) => true,
+        Msg::Stop => false,
+    };
+
+    loop {
+        match rx.recv() {
+            Ok(Msg::Stop) | Err(_) => return,
+            Ok(msg) if relevant(&msg) => (),
+            Ok(_) => continue,
+        }
+        loop {
+            match rx.recv_timeout(debounce) {
+                Ok(Msg::Stop) | Err(RecvTimeoutError::Disconnected) => return,
+                Ok(_) => continue,
+                Err(RecvTimeoutError::Timeout) => break,
+            }
+        }
+
+        if let Err(e) = inner.shared.reload() {
+            for f in lock(&inner.on_error).iter_mut() {
+                let _ = catch_unwind(AssertUnwindSafe(|| f(&e)));
+            }
+        }
+    }
+}
+
+fn watch_error(e: notify::Error) -> OVConfigError {
+    OVConfigError::IoError(std::io::Error::other(format!("watch failed: {}", e)))
+}